@@ -0,0 +1,240 @@
+use std::fmt::Write;
+
+use crate::parser::{Expr, ExprKind, FunctionDecl, Stmt, StmtKind};
+use crate::token::{Token, TokenType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AstStyle {
+    // One parenthesized line per tree, e.g. `(* (- 123) (group 45.67))`.
+    SExpr,
+    // One node per line, children indented under their parent. A doc
+    // comment comes first among the children of what it documents.
+    Tree,
+}
+
+pub fn print_expr(expr: &Expr<'_>, style: AstStyle) -> String {
+    render(&expr_node(expr), style)
+}
+
+pub fn print_program(statements: &[Stmt<'_>], style: AstStyle) -> String {
+    statements
+        .iter()
+        .map(|stmt| render(&stmt_node(stmt), style))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Both styles are drawn from the same simplified tree, so they can't
+// disagree about how something was parsed.
+enum Node {
+    Leaf(String),
+    Branch(String, Vec<Node>),
+    // Only drawn in the tree view.
+    Doc(String),
+}
+
+impl Node {
+    fn leaf(label: &str) -> Node {
+        Node::Leaf(label.to_string())
+    }
+
+    fn new(label: &str, children: Vec<Node>) -> Node {
+        Node::Branch(label.to_string(), children)
+    }
+
+    fn documented(label: &str, doc: &Option<String>, mut children: Vec<Node>) -> Node {
+        if let Some(doc) = doc {
+            children.insert(0, Node::Doc(doc.clone()));
+        }
+        Node::new(label, children)
+    }
+}
+
+fn render(node: &Node, style: AstStyle) -> String {
+    let mut out = String::new();
+    match style {
+        AstStyle::SExpr => write_sexpr(&mut out, node),
+        AstStyle::Tree => write_tree(&mut out, node, 0),
+    }
+    out
+}
+
+fn write_sexpr(out: &mut String, node: &Node) {
+    match node {
+        Node::Leaf(label) => out.push_str(label),
+        Node::Branch(label, children) => {
+            out.push('(');
+            out.push_str(label);
+            for child in children {
+                if let Node::Doc(_) = child {
+                    continue;
+                }
+                out.push(' ');
+                write_sexpr(out, child);
+            }
+            out.push(')');
+        }
+        Node::Doc(_) => (),
+    }
+}
+
+fn write_tree(out: &mut String, node: &Node, depth: usize) {
+    if depth > 0 {
+        out.push('\n');
+    }
+    let indent = "  ".repeat(depth);
+    let (label, children) = match node {
+        Node::Leaf(label) => (label, &[][..]),
+        Node::Branch(label, children) => (label, &children[..]),
+        Node::Doc(doc) => {
+            let lines = doc.lines().map(|line| format!("{}/// {}", indent, line));
+            out.push_str(&lines.collect::<Vec<_>>().join("\n"));
+            return;
+        }
+    };
+    let _ = write!(out, "{}{}", indent, label);
+    for child in children {
+        write_tree(out, child, depth + 1);
+    }
+}
+
+fn expr_node(expr: &Expr<'_>) -> Node {
+    match &expr.kind {
+        ExprKind::Assign(name, value, _) => {
+            Node::new("=", vec![Node::leaf(&name.lexeme), expr_node(value)])
+        }
+        ExprKind::Binary(left, operator, right) | ExprKind::Logical(left, operator, right) => {
+            Node::new(&operator.lexeme, vec![expr_node(left), expr_node(right)])
+        }
+        ExprKind::Call(callee, _, arguments) => {
+            let mut children = vec![expr_node(callee)];
+            children.extend(arguments.iter().map(expr_node));
+            Node::new("call", children)
+        }
+        ExprKind::Get(object, name) => get_node(object, name),
+        ExprKind::Grouping(expr) => Node::new("group", vec![expr_node(expr)]),
+        ExprKind::Literal(token) => Node::leaf(&literal(token)),
+        ExprKind::Set(object, name, value) => {
+            Node::new("=", vec![get_node(object, name), expr_node(value)])
+        }
+        ExprKind::Super(_, method, _) => Node::leaf(&format!("super.{}", method.lexeme)),
+        ExprKind::This(_, _) => Node::leaf("this"),
+        ExprKind::Unary(operator, right) => Node::new(&operator.lexeme, vec![expr_node(right)]),
+        ExprKind::Variable(name, _) => Node::leaf(&name.lexeme),
+    }
+}
+
+fn get_node(object: &Expr<'_>, name: &Token<'_>) -> Node {
+    Node::new(".", vec![expr_node(object), Node::leaf(&name.lexeme)])
+}
+
+// Strings are quoted so they can't be mistaken for variables.
+fn literal(token: &Token<'_>) -> String {
+    match &token.token_type {
+        TokenType::Number(n) => n.to_string(),
+        TokenType::String(s) => format!("{:?}", s),
+        TokenType::Bool(b) => b.to_string(),
+        _ => token.lexeme.to_string(),
+    }
+}
+
+fn stmt_node(stmt: &Stmt<'_>) -> Node {
+    match &stmt.kind {
+        StmtKind::Block(statements) => {
+            Node::new("block", statements.iter().map(stmt_node).collect())
+        }
+        StmtKind::Class(name, superclass, methods) => {
+            let mut children = vec![Node::leaf(&name.lexeme)];
+            if let Some(superclass) = superclass {
+                children.push(Node::new("<", vec![expr_node(superclass)]));
+            }
+            children.extend(methods.iter().map(|method| function_node(method)));
+            Node::documented("class", &stmt.doc, children)
+        }
+        StmtKind::Expression(expr) => Node::new("expr", vec![expr_node(expr)]),
+        StmtKind::Function(declaration) => function_node(declaration),
+        StmtKind::If(condition, then_branch, else_branch) => {
+            let mut children = vec![expr_node(condition), stmt_node(then_branch)];
+            if let Some(else_branch) = else_branch {
+                children.push(stmt_node(else_branch));
+            }
+            Node::new("if", children)
+        }
+        StmtKind::Print(expr) => Node::new("print", vec![expr_node(expr)]),
+        StmtKind::Return(_, value) => Node::new("return", value.iter().map(expr_node).collect()),
+        StmtKind::Var(name, initializer) => {
+            let mut children = vec![Node::leaf(&name.lexeme)];
+            children.extend(initializer.iter().map(expr_node));
+            Node::documented("var", &stmt.doc, children)
+        }
+        StmtKind::While(condition, body) => {
+            Node::new("while", vec![expr_node(condition), stmt_node(body)])
+        }
+    }
+}
+
+// The parameter list is always a branch, so a function without parameters
+// still shows an empty `(params)`.
+fn function_node(declaration: &FunctionDecl) -> Node {
+    let params = declaration
+        .params
+        .iter()
+        .map(|param| Node::leaf(&param.lexeme))
+        .collect();
+    let mut children = vec![
+        Node::leaf(&declaration.name.lexeme),
+        Node::new("params", params),
+    ];
+    children.extend(declaration.body.iter().map(stmt_node));
+    Node::documented("fun", &declaration.doc, children)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn program(source: &str, style: AstStyle) -> String {
+        let (statements, errors) = Parser::new(Scanner::new(source)).parse();
+        assert!(errors.is_empty(), "{:?}", errors);
+        print_program(&statements, style)
+    }
+
+    #[test]
+    fn sexpr_shows_precedence() {
+        let expr = Parser::new(Scanner::new("-123 * (45.67)"))
+            .parse_expression()
+            .unwrap();
+        assert_eq!(
+            print_expr(&expr, AstStyle::SExpr),
+            "(* (- 123) (group 45.67))"
+        );
+        assert_eq!(
+            program("a = b or c and !d;", AstStyle::SExpr),
+            "(expr (= a (or b (and c (! d)))))"
+        );
+    }
+
+    #[test]
+    fn sexpr_leaves_out_doc_comments() {
+        assert_eq!(
+            program(
+                "/// Doc for A\nclass A < B { m(x) { return this.x; } }",
+                AstStyle::SExpr
+            ),
+            "(class A (< B) (fun m (params x) (return (. this x))))"
+        );
+    }
+
+    #[test]
+    fn tree_indents_children_under_documented_class() {
+        assert_eq!(
+            program(
+                "/// Doc for A\nclass A {\n  /// Doc for m\n  m() { print \"hi\"; }\n}",
+                AstStyle::Tree
+            ),
+            "class\n  /// Doc for A\n  A\n  fun\n    /// Doc for m\n    m\n    params\n    print\n      \"hi\""
+        );
+    }
+}
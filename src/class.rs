@@ -0,0 +1,137 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::error::{RuntimeError, RuntimeErrorKind};
+use crate::function::{Callable, LoxFunction};
+use crate::interpreter::{Interpreter, RuntimeValue};
+use crate::token::Token;
+
+pub struct LoxClass {
+    pub name: String,
+    superclass: Option<Rc<LoxClass>>,
+    methods: HashMap<String, Rc<LoxFunction>>,
+}
+
+impl LoxClass {
+    pub fn new(
+        name: &str,
+        superclass: Option<Rc<LoxClass>>,
+        methods: HashMap<String, Rc<LoxFunction>>,
+    ) -> LoxClass {
+        LoxClass {
+            name: name.to_string(),
+            superclass,
+            methods,
+        }
+    }
+
+    pub fn method_names(&self) -> Vec<String> {
+        let mut names = match &self.superclass {
+            Some(superclass) => superclass.method_names(),
+            None => Vec::new(),
+        };
+        names.extend(self.methods.keys().cloned());
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    pub fn find_method(&self, name: &str) -> Option<Rc<LoxFunction>> {
+        if let Some(method) = self.methods.get(name) {
+            return Some(Rc::clone(method));
+        }
+
+        self.superclass
+            .as_ref()
+            .and_then(|superclass| superclass.find_method(name))
+    }
+}
+
+// Instances need to hold on to their class, so calling is implemented on the
+// shared handle rather than on the class itself.
+impl Callable for Rc<LoxClass> {
+    fn arity(&self) -> usize {
+        self.find_method("init")
+            .map_or(0, |initializer| initializer.arity())
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<RuntimeValue>,
+    ) -> Result<RuntimeValue, RuntimeError<'static>> {
+        let instance = Rc::new(RefCell::new(LoxInstance::new(Rc::clone(self))));
+        if let Some(initializer) = self.find_method("init") {
+            initializer
+                .bind(Rc::clone(&instance))
+                .call(interpreter, arguments)?;
+        }
+
+        Ok(RuntimeValue::Instance(instance))
+    }
+}
+
+impl fmt::Debug for LoxClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<class {}>", self.name)
+    }
+}
+
+pub struct LoxInstance {
+    pub class: Rc<LoxClass>,
+    fields: HashMap<String, RuntimeValue>,
+}
+
+impl LoxInstance {
+    pub fn new(class: Rc<LoxClass>) -> LoxInstance {
+        LoxInstance {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    pub fn get<'src>(
+        instance: &Rc<RefCell<LoxInstance>>,
+        name: &Token<'src>,
+    ) -> Result<RuntimeValue, RuntimeError<'src>> {
+        if let Some(value) = instance.borrow().fields.get(name.lexeme.as_str()) {
+            return Ok(value.clone());
+        }
+
+        let method = instance.borrow().class.find_method(name.lexeme.as_str());
+        match method {
+            Some(method) => Ok(RuntimeValue::Function(Rc::new(
+                method.bind(Rc::clone(instance)),
+            ))),
+            None => Err(RuntimeError::new(
+                RuntimeErrorKind::UndefinedProperty,
+                name,
+                &format!("Undefined property '{}'.", name.lexeme),
+            )),
+        }
+    }
+
+    // Fields followed by methods, including inherited ones, without
+    // duplicates.
+    pub fn property_names(&self) -> Vec<String> {
+        let mut names = self.fields.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        let mut methods = self.class.method_names();
+        methods.retain(|name| !names.contains(name));
+        names.extend(methods);
+        names
+    }
+
+    pub fn set<'src>(&mut self, name: &Token<'src>, value: RuntimeValue) {
+        self.fields.insert(name.lexeme.to_string(), value);
+    }
+}
+
+// Fields can refer back to the instance, so only the class name is shown.
+impl fmt::Debug for LoxInstance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<instance {}>", self.class.name)
+    }
+}
@@ -0,0 +1,351 @@
+use std::fmt::Write;
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::error::LoxError;
+use crate::token::Span;
+
+const RESET: &str = "\x1b[0m";
+const BOLD_RED: &str = "\x1b[1;31m";
+const BOLD_BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+        }
+    }
+}
+
+// A secondary location attached to a diagnostic, e.g. the '(' that a missing
+// ')' was supposed to close.
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Label {
+    pub fn new(span: Span, message: &str) -> Label {
+        Label {
+            span,
+            message: message.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub span: Span,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: &str, span: Span) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            code,
+            message: message.to_string(),
+            span,
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn with_label(mut self, label: Label) -> Diagnostic {
+        self.labels.push(label);
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Diagnostic {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    // Renders the diagnostic rustc-style: a header, the offending source line
+    // with the span underlined by carets, any labels, and trailing notes.
+    pub fn render(&self, source: &str, file: &str, color: bool) -> String {
+        let paint = |style: &str, text: &str| {
+            if color {
+                format!("{}{}{}", style, text, RESET)
+            } else {
+                text.to_string()
+            }
+        };
+
+        let mut lines = vec![self.span.line];
+        for label in &self.labels {
+            lines.push(label.span.line);
+        }
+        lines.sort_unstable();
+        lines.dedup();
+
+        let width = lines.last().map_or(1, |line| line.to_string().len());
+        let gutter = " ".repeat(width);
+        let bar = paint(BOLD_BLUE, "|");
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{}{}",
+            paint(BOLD_RED, self.severity.as_str()),
+            paint(BOLD, &format!(": {}", self.message))
+        );
+        let _ = writeln!(
+            out,
+            "{}{} {}:{}:{}",
+            gutter,
+            paint(BOLD_BLUE, "-->"),
+            file,
+            self.span.line,
+            self.span.column
+        );
+        let _ = writeln!(out, "{} {}", gutter, bar);
+
+        let mut previous = None;
+        for line in lines {
+            if previous.is_some_and(|previous| line > previous + 1) {
+                let _ = writeln!(out, "{}", paint(BOLD_BLUE, "..."));
+            }
+            previous = Some(line);
+
+            let text = source_line(source, line);
+            let _ = writeln!(
+                out,
+                "{} {} {}",
+                paint(BOLD_BLUE, &format!("{:>width$}", line)),
+                bar,
+                text
+            );
+
+            if self.span.line == line {
+                if let Some(marker) = underline(source, text, self.span, '^') {
+                    let _ = writeln!(out, "{} {} {}", gutter, bar, paint(BOLD_RED, &marker));
+                }
+            }
+            for label in self.labels.iter().filter(|label| label.span.line == line) {
+                let Some(marker) = underline(source, text, label.span, '-') else {
+                    continue;
+                };
+                let _ = writeln!(
+                    out,
+                    "{} {} {}",
+                    gutter,
+                    bar,
+                    paint(BOLD_BLUE, &format!("{} {}", marker, label.message))
+                );
+            }
+        }
+
+        for note in &self.notes {
+            let _ = writeln!(out, "{} {} note: {}", gutter, paint(BOLD_BLUE, "="), note);
+        }
+
+        out
+    }
+
+    // Serializes the diagnostic as a single-line JSON object. The field names
+    // are part of the CLI's stable output format, so only ever add to them.
+    pub fn to_json(&self, file: &str) -> String {
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "{{\"severity\":{},\"code\":{},\"message\":{},\"file\":{},\"line\":{},\"column\":{},\"span\":{}",
+            json_string(self.severity.as_str()),
+            json_string(self.code),
+            json_string(&self.message),
+            json_string(file),
+            self.span.line,
+            self.span.column,
+            json_span(self.span)
+        );
+
+        out.push_str(",\"labels\":[");
+        for (i, label) in self.labels.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(
+                out,
+                "{{\"message\":{},\"line\":{},\"column\":{},\"span\":{}}}",
+                json_string(&label.message),
+                label.span.line,
+                label.span.column,
+                json_span(label.span)
+            );
+        }
+        out.push_str("],\"notes\":[");
+        for (i, note) in self.notes.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str(&json_string(note));
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+impl From<&LoxError<'_>> for Diagnostic {
+    fn from(error: &LoxError<'_>) -> Self {
+        let mut diagnostic = Diagnostic::error(error.code(), error.message(), error.span());
+        if let LoxError::Parse(e) = error {
+            if let Some(label) = &e.label {
+                diagnostic = diagnostic.with_label(Label::clone(label));
+            }
+            if let Some(note) = e.kind.note() {
+                diagnostic = diagnostic.with_note(note);
+            }
+        }
+        diagnostic
+    }
+}
+
+fn json_span(span: Span) -> String {
+    format!("{{\"offset\":{},\"length\":{}}}", span.offset, span.len)
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn source_line(source: &str, line: u32) -> &str {
+    source
+        .lines()
+        .nth(line.saturating_sub(1) as usize)
+        .unwrap_or("")
+}
+
+// Builds the marker line under `text`, keeping tabs and padding wide
+// characters so the markers line up with what they point at. Spans running
+// past the end of the line are clipped to it. A span that doesn't start on
+// `text`, such as one into some other source, gets no marker line at all.
+fn underline(source: &str, text: &str, span: Span, marker: char) -> Option<String> {
+    let line_start = source.get(..span.offset)?.rfind('\n').map_or(0, |i| i + 1);
+    let prefix = text.get(..span.offset - line_start)?;
+
+    let mut out = String::new();
+    for c in prefix.chars() {
+        if c == '\t' {
+            out.push('\t');
+        } else {
+            out.extend(std::iter::repeat_n(' ', c.width().unwrap_or(0)));
+        }
+    }
+
+    let mut end = (prefix.len() + span.len).min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let count = text[prefix.len()..end].width().max(1);
+    out.extend(std::iter::repeat_n(marker, count));
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    #[test]
+    fn to_json_writes_every_field() {
+        let diagnostic = Diagnostic::error(
+            "parse/expected-token",
+            "Expect ')' after expression.",
+            Span::new(8, 1, 2, 3),
+        )
+        .with_label(Label::new(Span::new(4, 1, 1, 5), "opened here"))
+        .with_note("a note");
+
+        assert_eq!(
+            diagnostic.to_json("main.lox"),
+            "{\"severity\":\"error\",\"code\":\"parse/expected-token\",\
+             \"message\":\"Expect ')' after expression.\",\"file\":\"main.lox\",\
+             \"line\":2,\"column\":3,\"span\":{\"offset\":8,\"length\":1},\
+             \"labels\":[{\"message\":\"opened here\",\"line\":1,\"column\":5,\
+             \"span\":{\"offset\":4,\"length\":1}}],\"notes\":[\"a note\"]}"
+        );
+    }
+
+    #[test]
+    fn to_json_escapes_strings() {
+        let diagnostic = Diagnostic::error(
+            "scan/unexpected-character",
+            "quote \" backslash \\ newline \n tab \t bell \u{7}",
+            Span::new(0, 1, 1, 1),
+        );
+
+        assert_eq!(
+            diagnostic.to_json("dir\\\"a\".lox"),
+            "{\"severity\":\"error\",\"code\":\"scan/unexpected-character\",\
+             \"message\":\"quote \\\" backslash \\\\ newline \\n tab \\t bell \\u0007\",\
+             \"file\":\"dir\\\\\\\"a\\\".lox\",\"line\":1,\"column\":1,\
+             \"span\":{\"offset\":0,\"length\":1},\"labels\":[],\"notes\":[]}"
+        );
+    }
+
+    #[test]
+    fn to_json_from_a_parse_error() {
+        let (_, errors) = Parser::new(Scanner::new("print 1")).parse();
+        let diagnostic = Diagnostic::from(&errors[0]);
+
+        assert_eq!(
+            diagnostic.to_json("<stdin>"),
+            "{\"severity\":\"error\",\"code\":\"parse/expected-token\",\
+             \"message\":\"Expect ';' after value.\",\"file\":\"<stdin>\",\
+             \"line\":1,\"column\":8,\"span\":{\"offset\":7,\"length\":0},\
+             \"labels\":[],\"notes\":[]}"
+        );
+    }
+
+    #[test]
+    fn render_underlines_wide_characters() {
+        let diagnostic = Diagnostic::error("runtime/type", "Bad.", Span::new(9, 1, 1, 6));
+        assert_eq!(
+            diagnostic.render("\"日本\" + 1;", "a.lox", false),
+            "error: Bad.\n --> a.lox:1:6\n  |\n1 | \"日本\" + 1;\n  |        ^\n"
+        );
+    }
+
+    #[test]
+    fn render_skips_markers_that_do_not_fit_the_line() {
+        let source = "print \"é\";";
+        // Inside the 'é', past the end of the source, and on another line.
+        for span in [
+            Span::new(8, 1, 1, 8),
+            Span::new(40, 2, 1, 40),
+            Span::new(3, 1, 2, 1),
+        ] {
+            let rendered =
+                Diagnostic::error("runtime/type", "Bad.", span).render(source, "a.lox", false);
+            assert!(!rendered.contains('^'), "{}", rendered);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::error::{RuntimeError, RuntimeErrorKind};
+use crate::interpreter::RuntimeValue;
+use crate::token::Token;
+
+#[derive(Debug, Default)]
+pub struct Environment {
+    values: HashMap<String, RuntimeValue>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new() -> Environment {
+        Environment {
+            values: HashMap::new(),
+            enclosing: None,
+        }
+    }
+
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Environment {
+        Environment {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    pub fn define(&mut self, name: &str, value: RuntimeValue) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = (&str, &RuntimeValue)> {
+        self.values
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+    }
+
+    pub fn get<'src>(&self, name: &Token<'src>) -> Result<RuntimeValue, RuntimeError<'src>> {
+        if let Some(value) = self.values.get(name.lexeme.as_str()) {
+            return Ok(value.clone());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get(name),
+            None => Err(RuntimeError::new(
+                RuntimeErrorKind::UndefinedVariable,
+                name,
+                &format!("Undefined variable '{}'.", name.lexeme),
+            )),
+        }
+    }
+
+    pub fn get_at(&self, distance: usize, name: &str) -> RuntimeValue {
+        if distance == 0 {
+            return self.values.get(name).cloned().unwrap_or(RuntimeValue::Nil);
+        }
+
+        self.ancestor(distance)
+            .borrow()
+            .values
+            .get(name)
+            .cloned()
+            .unwrap_or(RuntimeValue::Nil)
+    }
+
+    pub fn assign_at<'src>(&mut self, distance: usize, name: &Token<'src>, value: RuntimeValue) {
+        if distance == 0 {
+            self.values.insert(name.lexeme.to_string(), value);
+            return;
+        }
+
+        self.ancestor(distance)
+            .borrow_mut()
+            .values
+            .insert(name.lexeme.to_string(), value);
+    }
+
+    fn ancestor(&self, distance: usize) -> Rc<RefCell<Environment>> {
+        let mut environment = Rc::clone(
+            self.enclosing
+                .as_ref()
+                .expect("resolver produced a distance past the global scope"),
+        );
+        for _ in 1..distance {
+            let enclosing = Rc::clone(
+                environment
+                    .borrow()
+                    .enclosing
+                    .as_ref()
+                    .expect("resolver produced a distance past the global scope"),
+            );
+            environment = enclosing;
+        }
+        environment
+    }
+
+    pub fn assign<'src>(
+        &mut self,
+        name: &Token<'src>,
+        value: RuntimeValue,
+    ) -> Result<(), RuntimeError<'src>> {
+        if let Some(slot) = self.values.get_mut(name.lexeme.as_str()) {
+            *slot = value;
+            return Ok(());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => Err(RuntimeError::new(
+                RuntimeErrorKind::UndefinedVariable,
+                name,
+                &format!("Undefined variable '{}'.", name.lexeme),
+            )),
+        }
+    }
+}
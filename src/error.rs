@@ -0,0 +1,383 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::diagnostic::Label;
+use crate::token::{Source, Span, Token, TokenType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanErrorKind {
+    UnexpectedCharacter,
+    UnterminatedString,
+    InvalidEscape,
+    MalformedNumber,
+    UnterminatedComment,
+}
+
+impl ScanErrorKind {
+    // Stable identifiers for tooling; existing codes must never change.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ScanErrorKind::UnexpectedCharacter => "scan/unexpected-character",
+            ScanErrorKind::UnterminatedString => "scan/unterminated-string",
+            ScanErrorKind::InvalidEscape => "scan/invalid-escape",
+            ScanErrorKind::MalformedNumber => "scan/malformed-number",
+            ScanErrorKind::UnterminatedComment => "scan/unterminated-comment",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScanError {
+    pub kind: ScanErrorKind,
+    pub lexeme: String,
+    pub span: Span,
+    pub message: String,
+}
+
+impl ScanError {
+    pub fn new(kind: ScanErrorKind, lexeme: &str, span: Span, message: &str) -> ScanError {
+        ScanError {
+            kind,
+            lexeme: lexeme.to_string(),
+            span,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn line(&self) -> u32 {
+        self.span.line
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[line {}] Error: {}", self.line(), self.message)
+    }
+}
+
+impl Error for ScanError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    ExpectedToken,
+    ExpectedExpression,
+    InvalidAssignmentTarget,
+    TooManyArguments,
+    TooManyParameters,
+    InheritFromSelf,
+}
+
+impl ParseErrorKind {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseErrorKind::ExpectedToken => "parse/expected-token",
+            ParseErrorKind::ExpectedExpression => "parse/expected-expression",
+            ParseErrorKind::InvalidAssignmentTarget => "parse/invalid-assignment-target",
+            ParseErrorKind::TooManyArguments => "parse/too-many-arguments",
+            ParseErrorKind::TooManyParameters => "parse/too-many-parameters",
+            ParseErrorKind::InheritFromSelf => "parse/inherit-from-self",
+        }
+    }
+
+    pub fn note(&self) -> Option<&'static str> {
+        match self {
+            ParseErrorKind::InvalidAssignmentTarget => {
+                Some("only variables and properties can be assigned to")
+            }
+            ParseErrorKind::TooManyArguments | ParseErrorKind::TooManyParameters => {
+                Some("functions are limited to 255 parameters")
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ParseError<'src> {
+    pub kind: ParseErrorKind,
+    pub token: Token<'src>,
+    pub message: String,
+    // Boxed to keep `Result<_, ParseError>` small on the parser's hot path.
+    pub label: Option<Box<Label>>,
+}
+
+impl<'src> ParseError<'src> {
+    pub fn new(kind: ParseErrorKind, token: Token<'src>, message: &str) -> ParseError<'src> {
+        ParseError {
+            kind,
+            token,
+            message: message.to_string(),
+            label: None,
+        }
+    }
+
+    pub fn with_label(mut self, span: Span, message: &str) -> ParseError<'src> {
+        self.label = Some(Box::new(Label::new(span, message)));
+        self
+    }
+
+    pub fn line(&self) -> u32 {
+        self.token.line()
+    }
+
+    pub fn span(&self) -> Span {
+        self.token.span
+    }
+}
+
+impl fmt::Display for ParseError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[line {}] Error{}: {}",
+            self.line(),
+            location(&self.token),
+            self.message
+        )
+    }
+}
+
+impl Error for ParseError<'_> {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResolveErrorKind {
+    ReadInOwnInitializer,
+    AlreadyDeclared,
+    TopLevelReturn,
+    ReturnFromInitializer,
+    ThisOutsideClass,
+    SuperOutsideClass,
+    SuperWithoutSuperclass,
+}
+
+impl ResolveErrorKind {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ResolveErrorKind::ReadInOwnInitializer => "resolve/read-in-own-initializer",
+            ResolveErrorKind::AlreadyDeclared => "resolve/already-declared",
+            ResolveErrorKind::TopLevelReturn => "resolve/top-level-return",
+            ResolveErrorKind::ReturnFromInitializer => "resolve/return-from-initializer",
+            ResolveErrorKind::ThisOutsideClass => "resolve/this-outside-class",
+            ResolveErrorKind::SuperOutsideClass => "resolve/super-outside-class",
+            ResolveErrorKind::SuperWithoutSuperclass => "resolve/super-without-superclass",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolveError<'src> {
+    pub kind: ResolveErrorKind,
+    pub token: Token<'src>,
+    pub message: String,
+}
+
+impl<'src> ResolveError<'src> {
+    pub fn new(kind: ResolveErrorKind, token: Token<'src>, message: &str) -> ResolveError<'src> {
+        ResolveError {
+            kind,
+            token,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn line(&self) -> u32 {
+        self.token.line()
+    }
+
+    pub fn span(&self) -> Span {
+        self.token.span
+    }
+}
+
+impl fmt::Display for ResolveError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[line {}] Error{}: {}",
+            self.line(),
+            location(&self.token),
+            self.message
+        )
+    }
+}
+
+impl Error for ResolveError<'_> {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeErrorKind {
+    OperandMustBeNumber,
+    OperandsMustBeNumbers,
+    OperandsMustBeNumbersOrStrings,
+    UndefinedVariable,
+    UndefinedProperty,
+    NotCallable,
+    ArityMismatch,
+    OnlyInstancesHaveProperties,
+    OnlyInstancesHaveFields,
+    SuperclassMustBeClass,
+    StackOverflow,
+}
+
+impl RuntimeErrorKind {
+    pub fn code(&self) -> &'static str {
+        match self {
+            RuntimeErrorKind::OperandMustBeNumber => "runtime/operand-must-be-number",
+            RuntimeErrorKind::OperandsMustBeNumbers => "runtime/operands-must-be-numbers",
+            RuntimeErrorKind::OperandsMustBeNumbersOrStrings => {
+                "runtime/operands-must-be-numbers-or-strings"
+            }
+            RuntimeErrorKind::UndefinedVariable => "runtime/undefined-variable",
+            RuntimeErrorKind::UndefinedProperty => "runtime/undefined-property",
+            RuntimeErrorKind::NotCallable => "runtime/not-callable",
+            RuntimeErrorKind::ArityMismatch => "runtime/arity-mismatch",
+            RuntimeErrorKind::OnlyInstancesHaveProperties => {
+                "runtime/only-instances-have-properties"
+            }
+            RuntimeErrorKind::OnlyInstancesHaveFields => "runtime/only-instances-have-fields",
+            RuntimeErrorKind::SuperclassMustBeClass => "runtime/superclass-must-be-class",
+            RuntimeErrorKind::StackOverflow => "runtime/stack-overflow",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RuntimeError<'src> {
+    pub kind: RuntimeErrorKind,
+    pub token: Token<'src>,
+    pub message: String,
+}
+
+impl<'src> RuntimeError<'src> {
+    pub fn new(kind: RuntimeErrorKind, token: &Token<'src>, message: &str) -> RuntimeError<'src> {
+        RuntimeError {
+            kind,
+            token: token.clone(),
+            message: message.to_string(),
+        }
+    }
+
+    pub fn line(&self) -> u32 {
+        self.token.line()
+    }
+
+    pub fn span(&self) -> Span {
+        self.token.span
+    }
+}
+
+impl fmt::Display for RuntimeError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\n[line {}]", self.message, self.line())
+    }
+}
+
+impl Error for RuntimeError<'_> {}
+
+#[derive(Debug, Clone)]
+pub enum LoxError<'src> {
+    Scan(ScanError),
+    Parse(ParseError<'src>),
+    Resolve(ResolveError<'src>),
+    Runtime(RuntimeError<'src>),
+}
+
+impl LoxError<'_> {
+    pub fn line(&self) -> u32 {
+        match self {
+            LoxError::Scan(e) => e.line(),
+            LoxError::Parse(e) => e.line(),
+            LoxError::Resolve(e) => e.line(),
+            LoxError::Runtime(e) => e.line(),
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            LoxError::Scan(e) => e.span,
+            LoxError::Parse(e) => e.span(),
+            LoxError::Resolve(e) => e.span(),
+            LoxError::Runtime(e) => e.span(),
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            LoxError::Scan(e) => e.kind.code(),
+            LoxError::Parse(e) => e.kind.code(),
+            LoxError::Resolve(e) => e.kind.code(),
+            LoxError::Runtime(e) => e.kind.code(),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            LoxError::Scan(e) => &e.message,
+            LoxError::Parse(e) => &e.message,
+            LoxError::Resolve(e) => &e.message,
+            LoxError::Runtime(e) => &e.message,
+        }
+    }
+
+    // The input the error points into, when that is an earlier one whose
+    // function raised it rather than the source just run.
+    pub fn source(&self) -> Option<&Source> {
+        match self {
+            LoxError::Scan(_) => None,
+            LoxError::Parse(e) => e.token.lexeme.source(),
+            LoxError::Resolve(e) => e.token.lexeme.source(),
+            LoxError::Runtime(e) => e.token.lexeme.source(),
+        }
+    }
+
+    // Follows the sysexits.h conventions used by the reference implementation.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            LoxError::Scan(_) | LoxError::Parse(_) | LoxError::Resolve(_) => 65,
+            LoxError::Runtime(_) => 70,
+        }
+    }
+}
+
+impl fmt::Display for LoxError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoxError::Scan(e) => e.fmt(f),
+            LoxError::Parse(e) => e.fmt(f),
+            LoxError::Resolve(e) => e.fmt(f),
+            LoxError::Runtime(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for LoxError<'_> {}
+
+impl From<ScanError> for LoxError<'_> {
+    fn from(e: ScanError) -> Self {
+        LoxError::Scan(e)
+    }
+}
+
+impl<'src> From<ParseError<'src>> for LoxError<'src> {
+    fn from(e: ParseError<'src>) -> Self {
+        LoxError::Parse(e)
+    }
+}
+
+impl<'src> From<ResolveError<'src>> for LoxError<'src> {
+    fn from(e: ResolveError<'src>) -> Self {
+        LoxError::Resolve(e)
+    }
+}
+
+impl<'src> From<RuntimeError<'src>> for LoxError<'src> {
+    fn from(e: RuntimeError<'src>) -> Self {
+        LoxError::Runtime(e)
+    }
+}
+
+fn location(token: &Token<'_>) -> String {
+    match token.token_type {
+        TokenType::Eof => " at end".to_string(),
+        _ => format!(" at '{}'", token.lexeme),
+    }
+}
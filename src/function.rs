@@ -0,0 +1,112 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::class::LoxInstance;
+use crate::environment::Environment;
+use crate::error::RuntimeError;
+use crate::interpreter::{Interpreter, RuntimeValue};
+use crate::parser::FunctionDecl;
+
+pub trait Callable {
+    fn arity(&self) -> usize;
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<RuntimeValue>,
+    ) -> Result<RuntimeValue, RuntimeError<'static>>;
+}
+
+pub struct LoxFunction {
+    declaration: Rc<FunctionDecl>,
+    closure: Rc<RefCell<Environment>>,
+    is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn new(
+        declaration: Rc<FunctionDecl>,
+        closure: Rc<RefCell<Environment>>,
+        is_initializer: bool,
+    ) -> LoxFunction {
+        LoxFunction {
+            declaration,
+            closure,
+            is_initializer,
+        }
+    }
+
+    pub fn bind(&self, instance: Rc<RefCell<LoxInstance>>) -> LoxFunction {
+        let mut environment = Environment::with_enclosing(Rc::clone(&self.closure));
+        environment.define("this", RuntimeValue::Instance(instance));
+        LoxFunction::new(
+            Rc::clone(&self.declaration),
+            Rc::new(RefCell::new(environment)),
+            self.is_initializer,
+        )
+    }
+
+    pub fn name(&self) -> &str {
+        self.declaration.name.lexeme.as_str()
+    }
+}
+
+impl Callable for LoxFunction {
+    fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<RuntimeValue>,
+    ) -> Result<RuntimeValue, RuntimeError<'static>> {
+        let mut environment = Environment::with_enclosing(Rc::clone(&self.closure));
+        for (param, argument) in self.declaration.params.iter().zip(arguments) {
+            environment.define(param.lexeme.as_str(), argument);
+        }
+
+        let value = interpreter
+            .execute_block(&self.declaration.body, Rc::new(RefCell::new(environment)))?;
+
+        // Initializers always hand back the instance, even on an early `return;`.
+        if self.is_initializer {
+            return Ok(self.closure.borrow().get_at(0, "this"));
+        }
+        Ok(value.unwrap_or(RuntimeValue::Nil))
+    }
+}
+
+// Functions close over environments that may contain themselves, so the
+// derived impl would recurse forever.
+impl fmt::Debug for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<fn {}>", self.name())
+    }
+}
+
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: fn(&[RuntimeValue]) -> RuntimeValue,
+}
+
+impl Callable for NativeFunction {
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<RuntimeValue>,
+    ) -> Result<RuntimeValue, RuntimeError<'static>> {
+        Ok((self.function)(&arguments))
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
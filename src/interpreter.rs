@@ -1,17 +1,42 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
 use std::ops::{Div, Mul, Sub};
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::parser::Expr;
-use crate::token::TokenType;
+use crate::class::{LoxClass, LoxInstance};
+use crate::environment::Environment;
+use crate::error::{RuntimeError, RuntimeErrorKind};
+use crate::function::{Callable, LoxFunction, NativeFunction};
+use crate::parser::{Expr, ExprId, ExprKind, Stmt, StmtKind};
+use crate::token::{Token, TokenType};
 
-#[derive(Debug)]
-enum RuntimeValue {
+#[derive(Debug, Clone)]
+pub enum RuntimeValue {
     Number(f64),
     String(String),
     Boolean(bool),
+    Function(Rc<LoxFunction>),
+    NativeFunction(Rc<NativeFunction>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
     Nil,
 }
 
 impl RuntimeValue {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            RuntimeValue::Number(_) => "number",
+            RuntimeValue::String(_) => "string",
+            RuntimeValue::Boolean(_) => "boolean",
+            RuntimeValue::Function(_) | RuntimeValue::NativeFunction(_) => "function",
+            RuntimeValue::Class(_) => "class",
+            RuntimeValue::Instance(_) => "instance",
+            RuntimeValue::Nil => "nil",
+        }
+    }
+
     fn is_truthy(&self) -> bool {
         match self {
             RuntimeValue::Nil => false,
@@ -26,53 +51,380 @@ impl RuntimeValue {
             (RuntimeValue::Number(n1), RuntimeValue::Number(n2)) => *n1 == n2,
             (RuntimeValue::String(s1), RuntimeValue::String(s2)) => *s1 == s2,
             (RuntimeValue::Boolean(b1), RuntimeValue::Boolean(b2)) => *b1 == b2,
+            (RuntimeValue::Function(f1), RuntimeValue::Function(f2)) => Rc::ptr_eq(f1, &f2),
+            (RuntimeValue::NativeFunction(f1), RuntimeValue::NativeFunction(f2)) => {
+                Rc::ptr_eq(f1, &f2)
+            }
+            (RuntimeValue::Class(c1), RuntimeValue::Class(c2)) => Rc::ptr_eq(c1, &c2),
+            (RuntimeValue::Instance(i1), RuntimeValue::Instance(i2)) => Rc::ptr_eq(i1, &i2),
             _ => false,
         }
     }
 }
 
-pub struct Interpreter;
+// Follows Lox's stringify rules: integral numbers drop the trailing `.0` and
+// strings print without quotes.
+impl fmt::Display for RuntimeValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuntimeValue::Number(n) if n.is_infinite() => {
+                write!(f, "{}Infinity", if *n < 0.0 { "-" } else { "" })
+            }
+            RuntimeValue::Number(n) => write!(f, "{}", n),
+            RuntimeValue::String(s) => write!(f, "{}", s),
+            RuntimeValue::Boolean(b) => write!(f, "{}", b),
+            RuntimeValue::Function(function) => write!(f, "<fn {}>", function.name()),
+            RuntimeValue::NativeFunction(_) => write!(f, "<native fn>"),
+            RuntimeValue::Class(class) => write!(f, "<class {}>", class.name),
+            RuntimeValue::Instance(instance) => {
+                write!(f, "<instance {}>", instance.borrow().class.name)
+            }
+            RuntimeValue::Nil => write!(f, "nil"),
+        }
+    }
+}
+
+// Each Lox call takes several native frames, up to about 80 KiB in a debug
+// build, so this is kept low enough for an 8 MiB thread. Hosts with a bigger
+// stack can allow more with `Interpreter::with_max_call_depth`.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 64;
+
+pub struct Interpreter {
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    locals: HashMap<ExprId, usize>,
+    call_depth: usize,
+    max_call_depth: usize,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Interpreter {
     pub fn new() -> Interpreter {
-        Interpreter
+        Interpreter::with_max_call_depth(DEFAULT_MAX_CALL_DEPTH)
+    }
+
+    pub fn with_max_call_depth(max_call_depth: usize) -> Interpreter {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals.borrow_mut().define(
+            "clock",
+            RuntimeValue::NativeFunction(Rc::new(NativeFunction {
+                name: "clock",
+                arity: 0,
+                function: |_| {
+                    let now = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0.0, |duration| duration.as_secs_f64());
+                    RuntimeValue::Number(now)
+                },
+            })),
+        );
+
+        Interpreter {
+            environment: Rc::clone(&globals),
+            globals,
+            locals: HashMap::new(),
+            call_depth: 0,
+            max_call_depth,
+        }
+    }
+
+    // Every global binding, sorted by name.
+    pub fn globals(&self) -> Vec<(String, RuntimeValue)> {
+        let mut globals = self
+            .globals
+            .borrow()
+            .values()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect::<Vec<_>>();
+        globals.sort_by(|(a, _), (b, _)| a.cmp(b));
+        globals
+    }
+
+    pub fn resolve(&mut self, id: ExprId, depth: usize) {
+        self.locals.insert(id, depth);
     }
 
-    pub fn interpret(&mut self, expr: Expr) -> Result<(), anyhow::Error> {
-        let val = self.evaluate(expr)?;
-        println!("{:?}", val);
+    pub fn interpret<'src>(&mut self, statements: &[Stmt<'src>]) -> Result<(), RuntimeError<'src>> {
+        for statement in statements {
+            self.execute(statement)?;
+        }
         Ok(())
     }
 
-    fn evaluate(&mut self, expr: Expr) -> Result<RuntimeValue, anyhow::Error> {
-        match expr {
-            Expr::Literal(token) => match token.token_type {
-                TokenType::Number(n) => Ok(RuntimeValue::Number(n)),
-                TokenType::String(s) => Ok(RuntimeValue::String(s)),
-                TokenType::Bool(b) => Ok(RuntimeValue::Boolean(b)),
+    // Returns `Some` when a `return` statement was executed so the value can
+    // unwind through any enclosing blocks and loops up to the function call.
+    fn execute<'src>(
+        &mut self,
+        stmt: &Stmt<'src>,
+    ) -> Result<Option<RuntimeValue>, RuntimeError<'src>> {
+        match &stmt.kind {
+            StmtKind::Expression(expr) => {
+                self.evaluate(expr)?;
+            }
+            StmtKind::Print(expr) => {
+                let value = self.evaluate(expr)?;
+                println!("{}", value);
+            }
+            StmtKind::Var(name, initializer) => {
+                let value = match initializer {
+                    Some(expr) => self.evaluate(expr)?,
+                    None => RuntimeValue::Nil,
+                };
+                self.environment
+                    .borrow_mut()
+                    .define(name.lexeme.as_str(), value);
+            }
+            StmtKind::Block(statements) => {
+                let environment = Environment::with_enclosing(Rc::clone(&self.environment));
+                return self.execute_block(statements, Rc::new(RefCell::new(environment)));
+            }
+            StmtKind::If(condition, then_branch, else_branch) => {
+                if self.evaluate(condition)?.is_truthy() {
+                    return self.execute(then_branch);
+                } else if let Some(else_branch) = else_branch {
+                    return self.execute(else_branch);
+                }
+            }
+            StmtKind::While(condition, body) => {
+                while self.evaluate(condition)?.is_truthy() {
+                    if let Some(value) = self.execute(body)? {
+                        return Ok(Some(value));
+                    }
+                }
+            }
+            StmtKind::Function(declaration) => {
+                let function =
+                    LoxFunction::new(Rc::clone(declaration), Rc::clone(&self.environment), false);
+                self.environment.borrow_mut().define(
+                    declaration.name.lexeme.as_str(),
+                    RuntimeValue::Function(Rc::new(function)),
+                );
+            }
+            StmtKind::Class(name, superclass, declarations) => {
+                let superclass = match superclass {
+                    Some(expr) => match self.evaluate(expr)? {
+                        RuntimeValue::Class(class) => Some(class),
+                        _ => {
+                            let token = match &expr.kind {
+                                ExprKind::Variable(superclass_name, _) => superclass_name,
+                                _ => name,
+                            };
+                            return Err(RuntimeError::new(
+                                RuntimeErrorKind::SuperclassMustBeClass,
+                                token,
+                                "Superclass must be a class.",
+                            ));
+                        }
+                    },
+                    None => None,
+                };
+
+                self.environment
+                    .borrow_mut()
+                    .define(name.lexeme.as_str(), RuntimeValue::Nil);
+
+                let mut closure = Rc::clone(&self.environment);
+                if let Some(superclass) = &superclass {
+                    let mut environment = Environment::with_enclosing(closure);
+                    environment.define("super", RuntimeValue::Class(Rc::clone(superclass)));
+                    closure = Rc::new(RefCell::new(environment));
+                }
+
+                let mut methods = HashMap::new();
+                for method in declarations {
+                    let function = LoxFunction::new(
+                        Rc::clone(method),
+                        Rc::clone(&closure),
+                        method.name.lexeme.as_str() == "init",
+                    );
+                    methods.insert(method.name.lexeme.to_string(), Rc::new(function));
+                }
+
+                let class = LoxClass::new(name.lexeme.as_str(), superclass, methods);
+                self.environment
+                    .borrow_mut()
+                    .assign(name, RuntimeValue::Class(Rc::new(class)))?;
+            }
+            StmtKind::Return(_, value) => {
+                let value = match value {
+                    Some(expr) => self.evaluate(expr)?,
+                    None => RuntimeValue::Nil,
+                };
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    pub(crate) fn execute_block<'src>(
+        &mut self,
+        statements: &[Stmt<'src>],
+        environment: Rc<RefCell<Environment>>,
+    ) -> Result<Option<RuntimeValue>, RuntimeError<'src>> {
+        let previous = std::mem::replace(&mut self.environment, environment);
+        let mut result = Ok(None);
+        for statement in statements {
+            result = self.execute(statement);
+            if !matches!(result, Ok(None)) {
+                break;
+            }
+        }
+        self.environment = previous;
+        result
+    }
+
+    pub(crate) fn evaluate<'src>(
+        &mut self,
+        expr: &Expr<'src>,
+    ) -> Result<RuntimeValue, RuntimeError<'src>> {
+        match &expr.kind {
+            ExprKind::Literal(token) => match &token.token_type {
+                TokenType::Number(n) => Ok(RuntimeValue::Number(*n)),
+                TokenType::String(s) => Ok(RuntimeValue::String(s.to_string())),
+                TokenType::Bool(b) => Ok(RuntimeValue::Boolean(*b)),
+                TokenType::True => Ok(RuntimeValue::Boolean(true)),
+                TokenType::False => Ok(RuntimeValue::Boolean(false)),
                 TokenType::Nil => Ok(RuntimeValue::Nil),
-                _ => Err(anyhow::anyhow!("Invalid literal")),
+                _ => unreachable!("parser only produces literal tokens here"),
+            },
+            ExprKind::Grouping(g) => self.evaluate(g),
+            ExprKind::Variable(name, id) => self.look_up_variable(name, *id),
+            ExprKind::Assign(name, value, id) => {
+                let value = self.evaluate(value)?;
+                match self.locals.get(id) {
+                    Some(distance) => {
+                        self.environment
+                            .borrow_mut()
+                            .assign_at(*distance, name, value.clone());
+                    }
+                    None => self.globals.borrow_mut().assign(name, value.clone())?,
+                }
+                Ok(value)
+            }
+            ExprKind::Logical(left, token, right) => {
+                let left = self.evaluate(left)?;
+
+                match token.token_type {
+                    TokenType::Or if left.is_truthy() => Ok(left),
+                    TokenType::And if !left.is_truthy() => Ok(left),
+                    _ => self.evaluate(right),
+                }
+            }
+            ExprKind::Call(callee, paren, arguments) => {
+                let callee = self.evaluate(callee)?;
+
+                let mut values = Vec::new();
+                for argument in arguments {
+                    values.push(self.evaluate(argument)?);
+                }
+
+                let function: &dyn Callable = match &callee {
+                    RuntimeValue::Function(function) => function.as_ref(),
+                    RuntimeValue::NativeFunction(function) => function.as_ref(),
+                    RuntimeValue::Class(class) => class,
+                    _ => {
+                        return Err(RuntimeError::new(
+                            RuntimeErrorKind::NotCallable,
+                            paren,
+                            "Can only call functions and classes.",
+                        ))
+                    }
+                };
+
+                if values.len() != function.arity() {
+                    return Err(RuntimeError::new(
+                        RuntimeErrorKind::ArityMismatch,
+                        paren,
+                        &format!(
+                            "Expected {} arguments but got {}.",
+                            function.arity(),
+                            values.len()
+                        ),
+                    ));
+                }
+
+                if self.call_depth == self.max_call_depth {
+                    return Err(RuntimeError::new(
+                        RuntimeErrorKind::StackOverflow,
+                        paren,
+                        "Stack overflow.",
+                    ));
+                }
+                self.call_depth += 1;
+                let result = function.call(self, values);
+                self.call_depth -= 1;
+                result
+            }
+            ExprKind::Get(object, name) => match self.evaluate(object)? {
+                RuntimeValue::Instance(instance) => LoxInstance::get(&instance, name),
+                _ => Err(RuntimeError::new(
+                    RuntimeErrorKind::OnlyInstancesHaveProperties,
+                    name,
+                    "Only instances have properties.",
+                )),
             },
-            Expr::Grouping(g) => self.evaluate(*g),
-            Expr::Unary(token, right) => {
-                let right = self.evaluate(*right)?;
+            ExprKind::Set(object, name, value) => match self.evaluate(object)? {
+                RuntimeValue::Instance(instance) => {
+                    let value = self.evaluate(value)?;
+                    instance.borrow_mut().set(name, value.clone());
+                    Ok(value)
+                }
+                _ => Err(RuntimeError::new(
+                    RuntimeErrorKind::OnlyInstancesHaveFields,
+                    name,
+                    "Only instances have fields.",
+                )),
+            },
+            ExprKind::Super(_, method, id) => {
+                let distance = self.locals[id];
+                let superclass = match self.environment.borrow().get_at(distance, "super") {
+                    RuntimeValue::Class(class) => class,
+                    _ => unreachable!("resolver binds 'super' to a class"),
+                };
+                // "this" is always bound one scope inside the one holding "super".
+                let instance = match self.environment.borrow().get_at(distance - 1, "this") {
+                    RuntimeValue::Instance(instance) => instance,
+                    _ => unreachable!("resolver binds 'this' to an instance"),
+                };
+
+                match superclass.find_method(method.lexeme.as_str()) {
+                    Some(method) => Ok(RuntimeValue::Function(Rc::new(method.bind(instance)))),
+                    None => Err(RuntimeError::new(
+                        RuntimeErrorKind::UndefinedProperty,
+                        method,
+                        &format!("Undefined property '{}'.", method.lexeme),
+                    )),
+                }
+            }
+            ExprKind::This(keyword, id) => self.look_up_variable(keyword, *id),
+            ExprKind::Unary(token, right) => {
+                let right = self.evaluate(right)?;
                 match token.token_type {
                     TokenType::Bang => Ok(RuntimeValue::Boolean(!right.is_truthy())),
                     TokenType::Minus => match right {
                         RuntimeValue::Number(n) => Ok(RuntimeValue::Number(-n)),
-                        _ => Err(anyhow::anyhow!("Operand must be a number")),
+                        _ => Err(RuntimeError::new(
+                            RuntimeErrorKind::OperandMustBeNumber,
+                            token,
+                            "Operand must be a number.",
+                        )),
                     },
-                    _ => Err(anyhow::anyhow!("Invalid unary operator")),
+                    _ => unreachable!("parser only produces '!' and '-' unary operators"),
                 }
             }
-            Expr::Binary(left, token, right) => {
-                let left = self.evaluate(*left)?;
-                let right = self.evaluate(*right)?;
+            ExprKind::Binary(left, token, right) => {
+                let left = self.evaluate(left)?;
+                let right = self.evaluate(right)?;
 
                 match token.token_type {
-                    TokenType::Minus => self.eval_arithmetic_op(left, right, f64::sub),
-                    TokenType::Slash => self.eval_arithmetic_op(left, right, f64::mul),
-                    TokenType::Star => self.eval_arithmetic_op(left, right, f64::div),
+                    TokenType::Minus => self.eval_arithmetic_op(token, left, right, f64::sub),
+                    TokenType::Slash => self.eval_arithmetic_op(token, left, right, f64::div),
+                    TokenType::Star => self.eval_arithmetic_op(token, left, right, f64::mul),
                     TokenType::Plus => match (left, right) {
                         (RuntimeValue::Number(l), RuntimeValue::Number(r)) => {
                             Ok(RuntimeValue::Number(l + r))
@@ -80,41 +432,67 @@ impl Interpreter {
                         (RuntimeValue::String(l), RuntimeValue::String(r)) => {
                             Ok(RuntimeValue::String(l + &r))
                         }
-                        _ => Err(anyhow::anyhow!("Unsupported type for plus operator")),
+                        _ => Err(RuntimeError::new(
+                            RuntimeErrorKind::OperandsMustBeNumbersOrStrings,
+                            token,
+                            "Operands must be two numbers or two strings.",
+                        )),
                     },
-                    TokenType::Greater => self.eval_boolean_op(left, right, |l, r| l > r),
-                    TokenType::GreaterEqual => self.eval_boolean_op(left, right, |l, r| l >= r),
-                    TokenType::Less => self.eval_boolean_op(left, right, |l, r| l < r),
-                    TokenType::LessEqual => self.eval_boolean_op(left, right, |l, r| l <= r),
+                    TokenType::Greater => self.eval_boolean_op(token, left, right, |l, r| l > r),
+                    TokenType::GreaterEqual => {
+                        self.eval_boolean_op(token, left, right, |l, r| l >= r)
+                    }
+                    TokenType::Less => self.eval_boolean_op(token, left, right, |l, r| l < r),
+                    TokenType::LessEqual => self.eval_boolean_op(token, left, right, |l, r| l <= r),
                     TokenType::BangEqual => Ok(RuntimeValue::Boolean(!left.is_equal(right))),
                     TokenType::EqualEqual => Ok(RuntimeValue::Boolean(left.is_equal(right))),
-                    _ => Err(anyhow::anyhow!("Invalid binary expression")),
+                    _ => unreachable!("parser only produces binary operator tokens here"),
                 }
             }
         }
     }
 
-    fn eval_arithmetic_op<F>(
+    fn look_up_variable<'src>(
+        &self,
+        name: &Token<'src>,
+        id: ExprId,
+    ) -> Result<RuntimeValue, RuntimeError<'src>> {
+        match self.locals.get(&id) {
+            Some(distance) => Ok(self
+                .environment
+                .borrow()
+                .get_at(*distance, name.lexeme.as_str())),
+            None => self.globals.borrow().get(name),
+        }
+    }
+
+    fn eval_arithmetic_op<'src, F>(
         &self,
+        operator: &Token<'src>,
         l: RuntimeValue,
         r: RuntimeValue,
         f: F,
-    ) -> Result<RuntimeValue, anyhow::Error>
+    ) -> Result<RuntimeValue, RuntimeError<'src>>
     where
         F: FnOnce(f64, f64) -> f64,
     {
         match (l, r) {
             (RuntimeValue::Number(l), RuntimeValue::Number(r)) => Ok(RuntimeValue::Number(f(l, r))),
-            _ => Err(anyhow::anyhow!("Operands must be numbers")),
+            _ => Err(RuntimeError::new(
+                RuntimeErrorKind::OperandsMustBeNumbers,
+                operator,
+                "Operands must be numbers.",
+            )),
         }
     }
 
-    fn eval_boolean_op<F>(
+    fn eval_boolean_op<'src, F>(
         &self,
+        operator: &Token<'src>,
         l: RuntimeValue,
         r: RuntimeValue,
         f: F,
-    ) -> Result<RuntimeValue, anyhow::Error>
+    ) -> Result<RuntimeValue, RuntimeError<'src>>
     where
         F: FnOnce(f64, f64) -> bool,
     {
@@ -122,7 +500,11 @@ impl Interpreter {
             (RuntimeValue::Number(l), RuntimeValue::Number(r)) => {
                 Ok(RuntimeValue::Boolean(f(l, r)))
             }
-            _ => Err(anyhow::anyhow!("Operands must be numbers")),
+            _ => Err(RuntimeError::new(
+                RuntimeErrorKind::OperandsMustBeNumbers,
+                operator,
+                "Operands must be numbers.",
+            )),
         }
     }
 }
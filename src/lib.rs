@@ -1,4 +1,214 @@
+pub mod ast_printer;
+pub mod class;
+pub mod diagnostic;
+pub mod environment;
+pub mod error;
+pub mod function;
 pub mod interpreter;
 pub mod parser;
+pub mod resolver;
 pub mod scanner;
 pub mod token;
+
+use error::{LoxError, ScanErrorKind};
+use interpreter::{Interpreter, RuntimeValue};
+use parser::{Expr, Parser};
+use resolver::Resolver;
+use scanner::Scanner;
+use token::TokenType;
+
+// Functions the interpreter keeps from `source` point into a shared copy of
+// it named `file`, so the source can be dropped once this returns. Errors
+// borrow from it, unless `LoxError::source` says they come from elsewhere.
+pub fn run<'src>(
+    source: &'src str,
+    file: &str,
+    interpreter: &mut Interpreter,
+) -> Result<(), Vec<LoxError<'src>>> {
+    let mut parser = Parser::new(Scanner::new(source)).with_file(file);
+    let (statements, errors) = parser.parse();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    Resolver::new(interpreter)
+        .resolve(&statements)
+        .map_err(|errors| errors.into_iter().map(LoxError::from).collect::<Vec<_>>())?;
+    interpreter
+        .interpret(&statements)
+        .map_err(|e| vec![e.into()])
+}
+
+// Runs one chunk of interactive input. Input that is a lone expression, with
+// no trailing ';', is evaluated and its value returned so it can be echoed.
+pub fn run_line<'src>(
+    source: &'src str,
+    file: &str,
+    interpreter: &mut Interpreter,
+) -> Result<Option<RuntimeValue>, Vec<LoxError<'src>>> {
+    let Ok(expr) = Parser::new(Scanner::new(source)).parse_expression() else {
+        return run(source, file, interpreter).map(|()| None);
+    };
+    evaluate_expr(&expr, interpreter).map(Some)
+}
+
+// Evaluates `source` as a single expression. Anything else is reported as a
+// parse error without running any of it.
+pub fn evaluate<'src>(
+    source: &'src str,
+    interpreter: &mut Interpreter,
+) -> Result<RuntimeValue, Vec<LoxError<'src>>> {
+    let expr = Parser::new(Scanner::new(source)).parse_expression()?;
+    evaluate_expr(&expr, interpreter)
+}
+
+fn evaluate_expr<'src>(
+    expr: &Expr<'src>,
+    interpreter: &mut Interpreter,
+) -> Result<RuntimeValue, Vec<LoxError<'src>>> {
+    Resolver::new(interpreter)
+        .resolve_expr(expr)
+        .map_err(|errors| errors.into_iter().map(LoxError::from).collect::<Vec<_>>())?;
+    interpreter.evaluate(expr).map_err(|e| vec![e.into()])
+}
+
+// Tells the REPL to keep reading: input is incomplete while it `is_open`, or
+// when the only thing wrong with it is that it ends too early.
+pub fn is_incomplete(source: &str) -> bool {
+    if is_open(source) {
+        return true;
+    }
+
+    if Parser::new(Scanner::new(source)).parse_expression().is_ok() {
+        return false;
+    }
+    let (_, errors) = Parser::new(Scanner::new(source)).parse();
+    errors.iter().any(|error| match error {
+        LoxError::Parse(error) => error.token.token_type == TokenType::Eof,
+        _ => false,
+    })
+}
+
+// Whether a bracket, string or block comment is still left open.
+pub fn is_open(source: &str) -> bool {
+    let mut depth = 0;
+    for token in Scanner::new(source) {
+        match token {
+            Ok(token) => match token.token_type {
+                TokenType::LeftParen | TokenType::LeftBrace => depth += 1,
+                TokenType::RightParen | TokenType::RightBrace => depth -= 1,
+                _ => (),
+            },
+            Err(error) => {
+                if let ScanErrorKind::UnterminatedString | ScanErrorKind::UnterminatedComment =
+                    error.kind
+                {
+                    return true;
+                }
+            }
+        }
+    }
+    depth > 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::Diagnostic;
+
+    #[test]
+    fn open_brackets_continue() {
+        for source in ["{", "fun f() {\n  var a = 1;\n", "print (1 +", "f(1,\n"] {
+            assert!(is_open(source), "{:?}", source);
+            assert!(is_incomplete(source), "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn open_strings_and_comments_continue() {
+        for source in [
+            "print \"abc",
+            "print r\"abc",
+            "print r#\"a\"b",
+            "/* a /* b */",
+        ] {
+            assert!(is_open(source), "{:?}", source);
+            assert!(is_incomplete(source), "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn error_at_end_of_input_continues() {
+        for source in ["print 1", "var a =", "print 1 +\n"] {
+            assert!(!is_open(source), "{:?}", source);
+            assert!(is_incomplete(source), "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn stray_closing_bracket_does_not_continue() {
+        for source in ["print 1);", ")", "}\n"] {
+            assert!(!is_open(source), "{:?}", source);
+            assert!(!is_incomplete(source), "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn complete_input_does_not_continue() {
+        for source in [
+            "print 1;",
+            "1 + 2",
+            "// only a comment",
+            "/* only a comment */",
+            "/// only a doc comment",
+            "",
+        ] {
+            assert!(!is_open(source), "{:?}", source);
+            assert!(!is_incomplete(source), "{:?}", source);
+        }
+    }
+
+    // Runs on a thread the size of a typical main thread, as an embedder
+    // calling `run` would.
+    #[test]
+    fn default_call_depth_fits_an_8_mib_stack() {
+        let source = "fun f(n) {\n  if (n >= 0) {\n    while (true) { return 1 + (2 * f(n + 1)); }\n  }\n}\nf(0);";
+        let errors = std::thread::Builder::new()
+            .stack_size(8 * 1024 * 1024)
+            .spawn(move || {
+                run(source, "deep.lox", &mut Interpreter::new())
+                    .unwrap_err()
+                    .iter()
+                    .map(LoxError::code)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(errors, ["runtime/stack-overflow"]);
+    }
+
+    #[test]
+    fn runtime_error_points_into_the_input_that_declared_the_function() {
+        let mut interpreter = Interpreter::new();
+        let declaration = String::from("fun f() {\n  return nil + 1;\n}\n");
+        run_line(&declaration, "<line 1>", &mut interpreter).unwrap();
+        drop(declaration);
+
+        let call = "print \"ééééééééé\"; f();\n";
+        let errors = run_line(call, "<line 2>", &mut interpreter).unwrap_err();
+        let origin = errors[0].source().unwrap();
+        assert_eq!(origin.name, "<line 1>");
+        assert_eq!(
+            Diagnostic::from(&errors[0]).render(&origin.text, &origin.name, false),
+            "error: Operands must be two numbers or two strings.\n --> <line 1>:2:14\n  |\n\
+             2 |   return nil + 1;\n  |              ^\n"
+        );
+    }
+
+    #[test]
+    fn errors_in_the_current_input_have_no_other_source() {
+        let errors = run_line("print nil + 1;", "<line 1>", &mut Interpreter::new()).unwrap_err();
+        assert!(errors[0].source().is_none());
+    }
+}
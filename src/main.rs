@@ -1,64 +1,364 @@
 use std::{
-    env,
-    fs::File,
-    io::stdin,
-    io::{self, Write},
-    io::{BufRead, Read},
-    process,
+    env, fs,
+    io::{self, IsTerminal},
+    path::PathBuf,
+    process, thread,
+    time::Instant,
 };
 
-use roxy::interpreter::Interpreter;
+use anyhow::Context;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Editor, Helper};
+use unicode_ident::is_xid_continue;
+
+use roxy::ast_printer::{self, AstStyle};
+use roxy::diagnostic::Diagnostic;
+use roxy::error::LoxError;
+use roxy::interpreter::{Interpreter, RuntimeValue};
 use roxy::parser::Parser;
-use roxy::scanner::Scanner;
+use roxy::scanner::{Scanner, KEYWORDS};
+
+#[derive(Clone, Copy, PartialEq)]
+enum DiagnosticFormat {
+    Human,
+    Json,
+}
+
+const HELP: &str = "\
+Enter Lox statements, or an expression to print its value.
+Commands:
+  :help           show this message
+  :quit           leave the REPL
+  :load <file>    run a file in this session
+  :env            list global variables
+  :type <expr>    evaluate an expression and show its type
+  :ast <code>     show the parsed syntax tree as an S-expression
+  :tokens <code>  show the scanned tokens
+  :time <code>    run code and show how long it took
+  :reset          forget everything defined so far
+Tab completes names, Ctrl-R searches history, Ctrl-C clears the input and
+Ctrl-D leaves.";
+
+const COMMANDS: [&str; 9] = [
+    ":help", ":quit", ":load", ":env", ":type", ":ast", ":tokens", ":time", ":reset",
+];
+
+fn read_source(path: &str) -> Result<String, anyhow::Error> {
+    let bytes = fs::read(path).with_context(|| format!("could not read '{}'", path))?;
+    let mut source =
+        String::from_utf8(bytes).with_context(|| format!("'{}' is not valid UTF-8", path))?;
+    // Some editors start UTF-8 files with a byte order mark.
+    if source.starts_with('\u{feff}') {
+        source.drain(..'\u{feff}'.len_utf8());
+    }
+    Ok(source)
+}
+
+fn print_ast(path: &str, style: AstStyle, format: DiagnosticFormat) -> Result<(), anyhow::Error> {
+    let source = read_source(path)?;
+
+    let (statements, errors) = Parser::new(Scanner::new(&source)).parse();
+    if !errors.is_empty() {
+        report(&source, path, &errors, format);
+        process::exit(errors[0].exit_code());
+    }
+    println!("{}", ast_printer::print_program(&statements, style));
+    Ok(())
+}
+
+fn run_file(path: &str, format: DiagnosticFormat) -> Result<(), anyhow::Error> {
+    let source = read_source(path)?;
+
+    let mut interpreter = interpreter();
+    if let Err(errors) = roxy::run(&source, path, &mut interpreter) {
+        report(&source, path, &errors, format);
+        process::exit(errors[0].exit_code());
+    }
+    Ok(())
+}
+
+struct Repl {
+    interpreter: Interpreter,
+    format: DiagnosticFormat,
+}
+
+impl Repl {
+    fn new(format: DiagnosticFormat) -> Repl {
+        Repl {
+            interpreter: interpreter(),
+            format,
+        }
+    }
+
+    fn eval(&mut self, source: &str, file: &str) {
+        match roxy::run_line(source, file, &mut self.interpreter) {
+            Ok(Some(value)) => println!("{}", value),
+            Ok(None) => {}
+            Err(errors) => report(source, file, &errors, self.format),
+        }
+    }
+
+    // Runs a `:command` line, returning false when the session should end.
+    fn command(&mut self, line: &str) -> bool {
+        let (name, argument) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let argument = argument.trim();
+        match name {
+            ":help" => println!("{}", HELP),
+            ":quit" => return false,
+            ":load" if !argument.is_empty() => match read_source(argument) {
+                Ok(source) => self.eval(&source, argument),
+                Err(error) => eprintln!("{:#}", error),
+            },
+            ":env" => {
+                for (name, value) in self.interpreter.globals() {
+                    println!("{} = {}", name, value);
+                }
+            }
+            ":type" if !argument.is_empty() => {
+                match roxy::evaluate(argument, &mut self.interpreter) {
+                    Ok(RuntimeValue::Instance(instance)) => {
+                        println!("instance of {}", instance.borrow().class.name)
+                    }
+                    Ok(value) => println!("{}", value.type_name()),
+                    Err(errors) => report(argument, "<repl>", &errors, self.format),
+                }
+            }
+            ":ast" if !argument.is_empty() => {
+                match Parser::new(Scanner::new(argument)).parse_expression() {
+                    Ok(expr) => println!("{}", ast_printer::print_expr(&expr, AstStyle::SExpr)),
+                    Err(_) => match Parser::new(Scanner::new(argument)).parse() {
+                        (statements, errors) if errors.is_empty() => {
+                            println!(
+                                "{}",
+                                ast_printer::print_program(&statements, AstStyle::SExpr)
+                            )
+                        }
+                        (_, errors) => report(argument, "<repl>", &errors, self.format),
+                    },
+                }
+            }
+            ":tokens" if !argument.is_empty() => {
+                for token in Scanner::new(argument) {
+                    match token {
+                        Ok(token) => {
+                            println!("{}:{} {}", token.span.line, token.span.column, token)
+                        }
+                        Err(error) => report(argument, "<repl>", &[error.into()], self.format),
+                    }
+                }
+            }
+            ":time" if !argument.is_empty() => {
+                let start = Instant::now();
+                self.eval(argument, "<repl>");
+                println!("took {:?}", start.elapsed());
+            }
+            ":reset" => self.interpreter = interpreter(),
+            ":load" | ":type" | ":ast" | ":tokens" | ":time" => {
+                eprintln!("{} needs an argument; type :help for usage", name)
+            }
+            _ => eprintln!("Unknown command '{}'; type :help for a list", name),
+        }
+        true
+    }
+}
+
+// Completes commands, keywords and globals, and after `name.` the fields
+// and methods of the instance stored in that global.
+#[derive(Default)]
+struct ReplHelper {
+    globals: Vec<(String, RuntimeValue)>,
+}
+
+impl ReplHelper {
+    fn candidates(&self, before: &str, start: usize) -> Vec<String> {
+        if start == 0 && before.starts_with(':') {
+            return COMMANDS.iter().map(|command| command.to_string()).collect();
+        }
+        let Some(object) = before[..start].strip_suffix('.') else {
+            return KEYWORDS
+                .keys()
+                .map(|keyword| keyword.to_string())
+                .chain(self.globals.iter().map(|(name, _)| name.clone()))
+                .collect();
+        };
+        let name = &object[word_start(object)..];
+        match self.globals.iter().find(|(global, _)| global == name) {
+            Some((_, RuntimeValue::Instance(instance))) => instance.borrow().property_names(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+fn word_start(text: &str) -> usize {
+    text.char_indices()
+        .rev()
+        .take_while(|&(_, c)| is_xid_continue(c))
+        .last()
+        .map_or(text.len(), |(i, _)| i)
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let before = &line[..pos];
+        let start = if before.starts_with(':') && !before.contains(char::is_whitespace) {
+            0
+        } else {
+            word_start(before)
+        };
+        let prefix = &before[start..];
+        let mut matches = self
+            .candidates(before, start)
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(prefix))
+            .collect::<Vec<_>>();
+        matches.sort();
+        matches.dedup();
+        Ok((start, matches))
+    }
+}
 
-fn run_file(path: &str) -> Result<(), anyhow::Error> {
-    let mut f = File::open(path).expect("File not found");
-    let mut source = String::new();
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
 
-    f.read_to_string(&mut source).expect("Failed to read file");
+impl Helper for ReplHelper {}
 
-    run(&source)
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".roxy_history"))
 }
 
-fn run_prompt() -> Result<(), anyhow::Error> {
-    let mut handler = stdin().lock();
+fn run_prompt(format: DiagnosticFormat) -> Result<(), anyhow::Error> {
+    let mut editor = Editor::<ReplHelper, DefaultHistory>::new()?;
+    editor.set_helper(Some(ReplHelper::default()));
+    let history = history_path();
+    if let Some(history) = &history {
+        // There is nothing to load the first time the REPL runs.
+        let _ = editor.load_history(history);
+    }
+
+    let mut repl = Repl::new(format);
+    let mut input = String::new();
     loop {
-        print!("> ");
-        io::stdout().flush()?;
-        let mut source = String::new();
-        if handler.read_line(&mut source).is_err() || source.is_empty() {
-            break;
+        if let Some(helper) = editor.helper_mut() {
+            helper.globals = repl.interpreter.globals();
         }
-        match run(&source) {
-            Ok(_) => (),
-            Err(e) => println!("{:?}", e),
+        let prompt = if input.is_empty() { "> " } else { "... " };
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => {
+                input.clear();
+                continue;
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(error) => return Err(error.into()),
         };
+        if !line.trim().is_empty() {
+            editor.add_history_entry(line.as_str())?;
+        }
+
+        if input.is_empty() && line.trim_start().starts_with(':') {
+            if !repl.command(line.trim()) {
+                break;
+            }
+            continue;
+        }
+
+        // A blank line runs whatever has been typed so far, so a mistake
+        // can't leave the prompt stuck waiting for more. Inside an open
+        // bracket it is just part of a pasted snippet; Ctrl-C abandons it.
+        let blank = line.trim().is_empty();
+        input.push_str(&line);
+        input.push('\n');
+        if roxy::is_incomplete(&input) && (!blank || roxy::is_open(&input)) {
+            continue;
+        }
+
+        repl.eval(&input, "<repl>");
+        input.clear();
+    }
+
+    if let Some(history) = &history {
+        editor
+            .save_history(history)
+            .with_context(|| format!("could not save history to '{}'", history.display()))?;
     }
     Ok(())
 }
 
-fn run(source: &str) -> Result<(), anyhow::Error> {
-    let mut scanner = Scanner::new(source);
-    let mut interpreter = Interpreter::new();
-    let tokens = scanner.scan_tokens();
-    let mut parser = Parser::new(tokens);
-    let expr = parser
-        .parse()
-        .ok_or(anyhow::anyhow!("Failed to parse expression"))?;
-    let out = interpreter.interpret(expr)?;
-    println!("{:?}", out);
-    Ok(())
+fn report(source: &str, file: &str, errors: &[LoxError], format: DiagnosticFormat) {
+    let color = io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none();
+    for error in errors {
+        let diagnostic = Diagnostic::from(error);
+        // A function declared in an earlier input is shown where it was written.
+        let (source, file) = match error.source() {
+            Some(origin) => (origin.text.as_str(), origin.name.as_str()),
+            None => (source, file),
+        };
+        match format {
+            DiagnosticFormat::Human => eprintln!("{}", diagnostic.render(source, file, color)),
+            DiagnosticFormat::Json => eprintln!("{}", diagnostic.to_json(file)),
+        }
+    }
+}
+
+fn usage() -> ! {
+    println!("Usage: roxy [--diagnostics=human|json] [script]");
+    println!("       roxy [--diagnostics=human|json] ast [--tree] <script>");
+    process::exit(64);
+}
+
+// Lox calls recurse on the native stack, and debug builds use tens of
+// kilobytes per call, so the interpreter gets a thread with room for
+// `MAX_CALL_DEPTH` calls. Untouched pages are never committed.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+const MAX_CALL_DEPTH: usize = 1000;
+
+fn interpreter() -> Interpreter {
+    Interpreter::with_max_call_depth(MAX_CALL_DEPTH)
 }
 
 fn main() -> anyhow::Result<()> {
-    let args = env::args().skip(1).collect::<Vec<String>>();
-    if args.len() > 1 {
-        println!("Usage: roxy [script]");
-        process::exit(64);
-    } else if args.len() == 1 {
-        run_file(&args[0])?;
-    } else {
-        run_prompt()?;
+    thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(cli)?
+        .join()
+        .unwrap_or_else(|_| process::exit(101))
+}
+
+fn cli() -> anyhow::Result<()> {
+    let mut format = DiagnosticFormat::Human;
+    let mut args = Vec::new();
+    for arg in env::args().skip(1) {
+        match arg.strip_prefix("--diagnostics=") {
+            Some("human") => format = DiagnosticFormat::Human,
+            Some("json") => format = DiagnosticFormat::Json,
+            Some(_) => usage(),
+            None => args.push(arg),
+        }
+    }
+
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["ast", path] => print_ast(path, AstStyle::SExpr, format)?,
+        ["ast", "--tree", path] => print_ast(path, AstStyle::Tree, format)?,
+        [path] => run_file(path, format)?,
+        [] => run_prompt(format)?,
+        _ => usage(),
     }
     Ok(())
 }
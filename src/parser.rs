@@ -1,36 +1,675 @@
-use crate::token::{Token, TokenType};
+use std::borrow::Cow;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::error::{LoxError, ParseError, ParseErrorKind};
+use crate::scanner::Scanner;
+use crate::token::{Lexeme, Source, Span, Token, TokenType};
+
+// Identifies the expressions the resolver binds to a scope. Ids are unique
+// across parsers so resolutions from earlier runs stay valid.
+pub type ExprId = usize;
+
+static NEXT_EXPR_ID: AtomicUsize = AtomicUsize::new(0);
+
+fn next_expr_id() -> ExprId {
+    NEXT_EXPR_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone)]
+pub struct Expr<'src> {
+    pub kind: ExprKind<'src>,
+    pub span: Span,
+}
+
+impl<'src> Expr<'src> {
+    pub fn new(kind: ExprKind<'src>, span: Span) -> Expr<'src> {
+        Expr { kind, span }
+    }
+
+    // Points every borrowed lexeme into `source`, a shared copy of the text
+    // being parsed, keeping expression ids, so the tree no longer depends on
+    // the original source.
+    pub fn into_static(self, source: &Rc<Source>) -> Expr<'static> {
+        let kind = match self.kind {
+            ExprKind::Assign(name, value, id) => {
+                ExprKind::Assign(name.into_static(source), boxed(*value, source), id)
+            }
+            ExprKind::Binary(left, operator, right) => ExprKind::Binary(
+                boxed(*left, source),
+                operator.into_static(source),
+                boxed(*right, source),
+            ),
+            ExprKind::Call(callee, paren, arguments) => ExprKind::Call(
+                boxed(*callee, source),
+                paren.into_static(source),
+                arguments
+                    .into_iter()
+                    .map(|expr| expr.into_static(source))
+                    .collect(),
+            ),
+            ExprKind::Get(object, name) => {
+                ExprKind::Get(boxed(*object, source), name.into_static(source))
+            }
+            ExprKind::Grouping(expr) => ExprKind::Grouping(boxed(*expr, source)),
+            ExprKind::Literal(token) => ExprKind::Literal(token.into_static(source)),
+            ExprKind::Logical(left, operator, right) => ExprKind::Logical(
+                boxed(*left, source),
+                operator.into_static(source),
+                boxed(*right, source),
+            ),
+            ExprKind::Set(object, name, value) => ExprKind::Set(
+                boxed(*object, source),
+                name.into_static(source),
+                boxed(*value, source),
+            ),
+            ExprKind::Super(keyword, method, id) => {
+                ExprKind::Super(keyword.into_static(source), method.into_static(source), id)
+            }
+            ExprKind::This(keyword, id) => ExprKind::This(keyword.into_static(source), id),
+            ExprKind::Unary(operator, right) => {
+                ExprKind::Unary(operator.into_static(source), boxed(*right, source))
+            }
+            ExprKind::Variable(name, id) => ExprKind::Variable(name.into_static(source), id),
+        };
+        Expr::new(kind, self.span)
+    }
+}
+
+fn boxed(expr: Expr<'_>, source: &Rc<Source>) -> Box<Expr<'static>> {
+    Box::new(expr.into_static(source))
+}
+
+#[derive(Debug, Clone)]
+pub enum ExprKind<'src> {
+    Assign(Token<'src>, Box<Expr<'src>>, ExprId),
+    Binary(Box<Expr<'src>>, Token<'src>, Box<Expr<'src>>),
+    Call(Box<Expr<'src>>, Token<'src>, Vec<Expr<'src>>),
+    Get(Box<Expr<'src>>, Token<'src>),
+    Grouping(Box<Expr<'src>>),
+    Literal(Token<'src>),
+    Logical(Box<Expr<'src>>, Token<'src>, Box<Expr<'src>>),
+    Set(Box<Expr<'src>>, Token<'src>, Box<Expr<'src>>),
+    Super(Token<'src>, Token<'src>, ExprId),
+    This(Token<'src>, ExprId),
+    Unary(Token<'src>, Box<Expr<'src>>),
+    Variable(Token<'src>, ExprId),
+}
+
+#[derive(Debug, Clone)]
+pub struct Stmt<'src> {
+    pub kind: StmtKind<'src>,
+    pub span: Span,
+    // The `///` comment directly above a class or variable declaration.
+    // Functions keep theirs on `FunctionDecl`.
+    pub doc: Option<String>,
+}
+
+impl<'src> Stmt<'src> {
+    pub fn new(kind: StmtKind<'src>, span: Span) -> Stmt<'src> {
+        Stmt {
+            kind,
+            span,
+            doc: None,
+        }
+    }
+
+    pub fn with_doc(mut self, doc: Option<String>) -> Stmt<'src> {
+        self.doc = doc;
+        self
+    }
+
+    // See `Expr::into_static`. Nested functions were converted when they were
+    // parsed and are shared rather than copied.
+    pub fn into_static(self, source: &Rc<Source>) -> Stmt<'static> {
+        let kind = match self.kind {
+            StmtKind::Block(statements) => StmtKind::Block(
+                statements
+                    .into_iter()
+                    .map(|statement| statement.into_static(source))
+                    .collect(),
+            ),
+            StmtKind::Class(name, superclass, methods) => StmtKind::Class(
+                name.into_static(source),
+                superclass.map(|expr| expr.into_static(source)),
+                methods,
+            ),
+            StmtKind::Expression(expr) => StmtKind::Expression(expr.into_static(source)),
+            StmtKind::Function(declaration) => StmtKind::Function(declaration),
+            StmtKind::If(condition, then_branch, else_branch) => StmtKind::If(
+                condition.into_static(source),
+                Box::new(then_branch.into_static(source)),
+                else_branch.map(|else_branch| Box::new(else_branch.into_static(source))),
+            ),
+            StmtKind::Print(expr) => StmtKind::Print(expr.into_static(source)),
+            StmtKind::Return(keyword, value) => StmtKind::Return(
+                keyword.into_static(source),
+                value.map(|expr| expr.into_static(source)),
+            ),
+            StmtKind::Var(name, initializer) => StmtKind::Var(
+                name.into_static(source),
+                initializer.map(|expr| expr.into_static(source)),
+            ),
+            StmtKind::While(condition, body) => StmtKind::While(
+                condition.into_static(source),
+                Box::new(body.into_static(source)),
+            ),
+        };
+        Stmt {
+            kind,
+            span: self.span,
+            doc: self.doc,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
-pub enum Expr {
-    Binary(Box<Expr>, Token, Box<Expr>),
-    Grouping(Box<Expr>),
-    Literal(Token),
-    Unary(Token, Box<Expr>),
+pub enum StmtKind<'src> {
+    Block(Vec<Stmt<'src>>),
+    Class(Token<'src>, Option<Expr<'src>>, Vec<Rc<FunctionDecl>>),
+    Expression(Expr<'src>),
+    Function(Rc<FunctionDecl>),
+    If(Expr<'src>, Box<Stmt<'src>>, Option<Box<Stmt<'src>>>),
+    Print(Expr<'src>),
+    Return(Token<'src>, Option<Expr<'src>>),
+    Var(Token<'src>, Option<Expr<'src>>),
+    While(Expr<'src>, Box<Stmt<'src>>),
+}
+
+// Functions outlive the source they were declared in, so their tokens point
+// into a shared copy of it; see `Lexeme`.
+#[derive(Debug)]
+pub struct FunctionDecl {
+    pub name: Token<'static>,
+    pub params: Vec<Token<'static>>,
+    pub body: Vec<Stmt<'static>>,
+    pub doc: Option<String>,
 }
 
-pub struct Parser {
-    tokens: Vec<Token>,
-    current: usize,
+// Pulls tokens from the scanner one at a time; only the current and previous
+// tokens are ever held.
+pub struct Parser<'src> {
+    scanner: Scanner<'src>,
+    current: Token<'src>,
+    previous: Token<'src>,
+    // Lines of the doc comment sitting right before `current`.
+    doc: Vec<Cow<'src, str>>,
+    errors: Vec<LoxError<'src>>,
+    // Offset of the token right after a scan error. A syntax error there
+    // would only repeat the scan error, so it isn't reported.
+    quiet_at: Option<usize>,
+    file: String,
+    // Copied from the scanner's text the first time a function is parsed,
+    // and then shared by every function in the input.
+    source: Option<Rc<Source>>,
 }
 
-impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Parser {
-        Parser { tokens, current: 0 }
+impl<'src> Parser<'src> {
+    pub fn new(scanner: Scanner<'src>) -> Parser<'src> {
+        let placeholder = Token::new(TokenType::Eof, "", Span::default());
+        let mut parser = Parser {
+            scanner,
+            current: placeholder.clone(),
+            previous: placeholder,
+            doc: Vec::new(),
+            errors: Vec::new(),
+            quiet_at: None,
+            file: String::from("<input>"),
+            source: None,
+        };
+        parser.current = parser.next_token();
+        parser
+    }
+
+    // Names the input in errors raised by its functions after the parse.
+    pub fn with_file(mut self, file: &str) -> Parser<'src> {
+        self.file = file.to_string();
+        self
+    }
+
+    // Returns every statement that parsed cleanly along with all the scan and
+    // syntax errors found, in source order, so callers can report them
+    // together.
+    pub fn parse(&mut self) -> (Vec<Stmt<'src>>, Vec<LoxError<'src>>) {
+        let mut statements = Vec::new();
+        while !self.is_at_end() {
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
+        }
+
+        (statements, std::mem::take(&mut self.errors))
+    }
+
+    // Parses input that must consist of exactly one expression, such as a
+    // bare expression typed at the REPL.
+    pub fn parse_expression(&mut self) -> Result<Expr<'src>, Vec<LoxError<'src>>> {
+        let result = self.expression().and_then(|expr| {
+            if self.is_at_end() {
+                Ok(expr)
+            } else {
+                Err(ParseError::new(
+                    ParseErrorKind::ExpectedToken,
+                    self.peek().clone(),
+                    "Expect end of expression.",
+                ))
+            }
+        });
+        match result {
+            Ok(expr) if self.errors.is_empty() => Ok(expr),
+            Ok(_) => Err(std::mem::take(&mut self.errors)),
+            Err(error) => {
+                self.record(error);
+                Err(std::mem::take(&mut self.errors))
+            }
+        }
+    }
+
+    fn declaration(&mut self) -> Option<Stmt<'src>> {
+        match self.try_declaration() {
+            Ok(statement) => Some(statement),
+            Err(error) => {
+                self.record(error);
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    fn try_declaration(&mut self) -> Result<Stmt<'src>, ParseError<'src>> {
+        // Doc comments in front of anything other than a declaration are
+        // dropped.
+        let doc = self.take_doc();
+        let declaration = match self.current_token() {
+            Some(TokenType::Class) => {
+                self.advance();
+                self.class_declaration()
+            }
+            Some(TokenType::Fun) => {
+                let keyword = self.advance();
+                let function = self.function("function", doc)?;
+                return Ok(Stmt::new(
+                    StmtKind::Function(function),
+                    self.span_from(keyword.span),
+                ));
+            }
+            Some(TokenType::Var) => {
+                self.advance();
+                self.var_declaration()
+            }
+            _ => return self.statement(),
+        };
+        declaration.map(|declaration| declaration.with_doc(doc))
+    }
+
+    fn class_declaration(&mut self) -> Result<Stmt<'src>, ParseError<'src>> {
+        let keyword = self.previous();
+        let name = self.consume_identifier("Expect class name.")?;
+
+        let mut superclass = None;
+        if let Some(TokenType::Less) = self.current_token() {
+            self.advance();
+            let superclass_name = self.consume_identifier("Expect superclass name.")?;
+            if superclass_name.lexeme == name.lexeme {
+                self.errors.push(
+                    ParseError::new(
+                        ParseErrorKind::InheritFromSelf,
+                        superclass_name.clone(),
+                        "A class can't inherit from itself.",
+                    )
+                    .into(),
+                );
+            }
+            let span = superclass_name.span;
+            superclass = Some(Expr::new(
+                ExprKind::Variable(superclass_name, next_expr_id()),
+                span,
+            ));
+        }
+
+        let brace = self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
+
+        let mut methods = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            let doc = self.take_doc();
+            methods.push(self.function("method", doc)?);
+        }
+
+        self.consume_closing(
+            TokenType::RightBrace,
+            "Expect '}' after class body.",
+            &brace,
+        )?;
+        Ok(Stmt::new(
+            StmtKind::Class(name, superclass, methods),
+            self.span_from(keyword.span),
+        ))
+    }
+
+    fn function(
+        &mut self,
+        kind: &str,
+        doc: Option<String>,
+    ) -> Result<Rc<FunctionDecl>, ParseError<'src>> {
+        let name = self.consume_identifier(&format!("Expect {} name.", kind))?;
+        let paren = self.consume(
+            TokenType::LeftParen,
+            &format!("Expect '(' after {} name.", kind),
+        )?;
+
+        let mut params = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if params.len() >= 255 {
+                    self.errors.push(
+                        ParseError::new(
+                            ParseErrorKind::TooManyParameters,
+                            self.peek().clone(),
+                            "Can't have more than 255 parameters.",
+                        )
+                        .into(),
+                    );
+                }
+                params.push(self.consume_identifier("Expect parameter name.")?);
+
+                if !self.check(TokenType::Comma) {
+                    break;
+                }
+                self.advance();
+            }
+        }
+        self.consume_closing(
+            TokenType::RightParen,
+            "Expect ')' after parameters.",
+            &paren,
+        )?;
+
+        let brace = self.consume(
+            TokenType::LeftBrace,
+            &format!("Expect '{{' before {} body.", kind),
+        )?;
+        let body = self.block(&brace)?;
+
+        let source = self.shared_source();
+        Ok(Rc::new(FunctionDecl {
+            name: name.into_static(&source),
+            params: params
+                .into_iter()
+                .map(|param| param.into_static(&source))
+                .collect(),
+            body: body
+                .into_iter()
+                .map(|statement| statement.into_static(&source))
+                .collect(),
+            doc,
+        }))
+    }
+
+    fn var_declaration(&mut self) -> Result<Stmt<'src>, ParseError<'src>> {
+        let keyword = self.previous();
+        let name = self.consume_identifier("Expect variable name.")?;
+
+        let mut initializer = None;
+        if let Some(TokenType::Equal) = self.current_token() {
+            self.advance();
+            initializer = Some(self.expression()?);
+        }
+
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration.",
+        )?;
+        Ok(Stmt::new(
+            StmtKind::Var(name, initializer),
+            self.span_from(keyword.span),
+        ))
+    }
+
+    fn statement(&mut self) -> Result<Stmt<'src>, ParseError<'src>> {
+        match self.current_token() {
+            Some(TokenType::For) => {
+                self.advance();
+                self.for_statement()
+            }
+            Some(TokenType::If) => {
+                self.advance();
+                self.if_statement()
+            }
+            Some(TokenType::Print) => {
+                self.advance();
+                self.print_statement()
+            }
+            Some(TokenType::Return) => {
+                self.advance();
+                self.return_statement()
+            }
+            Some(TokenType::While) => {
+                self.advance();
+                self.while_statement()
+            }
+            Some(TokenType::LeftBrace) => {
+                let brace = self.advance();
+                let statements = self.block(&brace)?;
+                Ok(Stmt::new(
+                    StmtKind::Block(statements),
+                    self.span_from(brace.span),
+                ))
+            }
+            _ => self.expression_statement(),
+        }
+    }
+
+    fn for_statement(&mut self) -> Result<Stmt<'src>, ParseError<'src>> {
+        let keyword = self.previous();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
+
+        let initializer = match self.current_token() {
+            Some(TokenType::Semicolon) => {
+                self.advance();
+                None
+            }
+            Some(TokenType::Var) => {
+                self.advance();
+                Some(self.var_declaration()?)
+            }
+            _ => Some(self.expression_statement()?),
+        };
+
+        let condition = if self.check(TokenType::Semicolon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        let semicolon = self.consume(TokenType::Semicolon, "Expect ';' after loop condition.")?;
+
+        let increment = if self.check(TokenType::RightParen) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
+
+        let mut body = self.statement()?;
+        // Every statement synthesized by the desugaring covers the whole loop.
+        let span = self.span_from(keyword.span);
+
+        if let Some(increment) = increment {
+            let increment_span = increment.span;
+            let increment = Stmt::new(StmtKind::Expression(increment), increment_span);
+            body = Stmt::new(StmtKind::Block(vec![body, increment]), span);
+        }
+
+        let condition = condition.unwrap_or_else(|| {
+            let token = Token {
+                token_type: TokenType::True,
+                lexeme: Lexeme::Static("true"),
+                span: semicolon.span,
+            };
+            Expr::new(ExprKind::Literal(token), semicolon.span)
+        });
+        body = Stmt::new(StmtKind::While(condition, Box::new(body)), span);
+
+        if let Some(initializer) = initializer {
+            body = Stmt::new(StmtKind::Block(vec![initializer, body]), span);
+        }
+
+        Ok(body)
+    }
+
+    fn if_statement(&mut self) -> Result<Stmt<'src>, ParseError<'src>> {
+        let keyword = self.previous();
+        let paren = self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
+        let condition = self.expression()?;
+        self.consume_closing(
+            TokenType::RightParen,
+            "Expect ')' after if condition.",
+            &paren,
+        )?;
+
+        let then_branch = Box::new(self.statement()?);
+        let mut else_branch = None;
+        if let Some(TokenType::Else) = self.current_token() {
+            self.advance();
+            else_branch = Some(Box::new(self.statement()?));
+        }
+
+        Ok(Stmt::new(
+            StmtKind::If(condition, then_branch, else_branch),
+            self.span_from(keyword.span),
+        ))
+    }
+
+    fn print_statement(&mut self) -> Result<Stmt<'src>, ParseError<'src>> {
+        let keyword = self.previous();
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
+        Ok(Stmt::new(
+            StmtKind::Print(value),
+            self.span_from(keyword.span),
+        ))
     }
 
-    pub fn parse(&mut self) -> Option<Expr> {
-        let expression = match self.expression() {
-            Ok(expr) => Some(expr),
-            Err(_) => None,
+    fn return_statement(&mut self) -> Result<Stmt<'src>, ParseError<'src>> {
+        let keyword = self.previous();
+        let value = if self.check(TokenType::Semicolon) {
+            None
+        } else {
+            Some(self.expression()?)
         };
-        expression
+
+        self.consume(TokenType::Semicolon, "Expect ';' after return value.")?;
+        let span = self.span_from(keyword.span);
+        Ok(Stmt::new(StmtKind::Return(keyword, value), span))
+    }
+
+    fn expression_statement(&mut self) -> Result<Stmt<'src>, ParseError<'src>> {
+        let expr = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
+        let span = self.span_from(expr.span);
+        Ok(Stmt::new(StmtKind::Expression(expr), span))
+    }
+
+    fn while_statement(&mut self) -> Result<Stmt<'src>, ParseError<'src>> {
+        let keyword = self.previous();
+        let paren = self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
+        let condition = self.expression()?;
+        self.consume_closing(TokenType::RightParen, "Expect ')' after condition.", &paren)?;
+        let body = self.statement()?;
+
+        Ok(Stmt::new(
+            StmtKind::While(condition, Box::new(body)),
+            self.span_from(keyword.span),
+        ))
+    }
+
+    fn block(&mut self, brace: &Token<'src>) -> Result<Vec<Stmt<'src>>, ParseError<'src>> {
+        let mut statements = Vec::new();
+
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
+        }
+
+        self.consume_closing(TokenType::RightBrace, "Expect '}' after block.", brace)?;
+        Ok(statements)
+    }
+
+    fn expression(&mut self) -> Result<Expr<'src>, ParseError<'src>> {
+        self.assignment()
+    }
+
+    fn assignment(&mut self) -> Result<Expr<'src>, ParseError<'src>> {
+        let expr = self.or()?;
+
+        if let Some(TokenType::Equal) = self.current_token() {
+            let equals = self.advance();
+            let value = self.assignment()?;
+
+            let span = expr.span.to(value.span);
+            return match expr.kind {
+                ExprKind::Variable(name, _) => Ok(Expr::new(
+                    ExprKind::Assign(name, Box::new(value), next_expr_id()),
+                    span,
+                )),
+                ExprKind::Get(object, name) => Ok(Expr::new(
+                    ExprKind::Set(object, name, Box::new(value)),
+                    span,
+                )),
+                // The parser is not confused here, so report without synchronizing.
+                _ => {
+                    self.errors.push(
+                        ParseError::new(
+                            ParseErrorKind::InvalidAssignmentTarget,
+                            equals,
+                            "Invalid assignment target.",
+                        )
+                        .with_label(expr.span, "cannot assign to this expression")
+                        .into(),
+                    );
+                    Ok(expr)
+                }
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> Result<Expr<'src>, ParseError<'src>> {
+        let mut expr = self.and()?;
+
+        while let Some(TokenType::Or) = self.current_token() {
+            self.advance();
+            let operator = self.previous();
+            let right = self.and()?;
+            let span = expr.span.to(right.span);
+            expr = Expr::new(
+                ExprKind::Logical(Box::new(expr), operator, Box::new(right)),
+                span,
+            );
+        }
+
+        Ok(expr)
     }
 
-    fn expression(&mut self) -> Result<Expr, anyhow::Error> {
-        self.equality()
+    fn and(&mut self) -> Result<Expr<'src>, ParseError<'src>> {
+        let mut expr = self.equality()?;
+
+        while let Some(TokenType::And) = self.current_token() {
+            self.advance();
+            let operator = self.previous();
+            let right = self.equality()?;
+            let span = expr.span.to(right.span);
+            expr = Expr::new(
+                ExprKind::Logical(Box::new(expr), operator, Box::new(right)),
+                span,
+            );
+        }
+
+        Ok(expr)
     }
 
-    fn equality(&mut self) -> Result<Expr, anyhow::Error> {
+    fn equality(&mut self) -> Result<Expr<'src>, ParseError<'src>> {
         let mut expr = self.comparison()?;
 
         while let Some(token_type) = self.current_token() {
@@ -39,7 +678,11 @@ impl Parser {
                     self.advance();
                     let operator = self.previous();
                     let right = self.comparison()?;
-                    expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
+                    let span = expr.span.to(right.span);
+                    expr = Expr::new(
+                        ExprKind::Binary(Box::new(expr), operator, Box::new(right)),
+                        span,
+                    );
                 }
                 _ => break,
             }
@@ -48,7 +691,7 @@ impl Parser {
         Ok(expr)
     }
 
-    fn comparison(&mut self) -> Result<Expr, anyhow::Error> {
+    fn comparison(&mut self) -> Result<Expr<'src>, ParseError<'src>> {
         let mut expr = self.term()?;
 
         while let Some(token_type) = self.current_token() {
@@ -60,7 +703,11 @@ impl Parser {
                     self.advance();
                     let operator = self.previous();
                     let right = self.term()?;
-                    expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
+                    let span = expr.span.to(right.span);
+                    expr = Expr::new(
+                        ExprKind::Binary(Box::new(expr), operator, Box::new(right)),
+                        span,
+                    );
                 }
                 _ => break,
             }
@@ -69,7 +716,7 @@ impl Parser {
         Ok(expr)
     }
 
-    fn term(&mut self) -> Result<Expr, anyhow::Error> {
+    fn term(&mut self) -> Result<Expr<'src>, ParseError<'src>> {
         let mut expr = self.factor()?;
 
         while let Some(token_type) = self.current_token() {
@@ -78,7 +725,11 @@ impl Parser {
                     self.advance();
                     let operator = self.previous();
                     let right = self.factor()?;
-                    expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
+                    let span = expr.span.to(right.span);
+                    expr = Expr::new(
+                        ExprKind::Binary(Box::new(expr), operator, Box::new(right)),
+                        span,
+                    );
                 }
                 _ => break,
             }
@@ -87,7 +738,7 @@ impl Parser {
         Ok(expr)
     }
 
-    fn factor(&mut self) -> Result<Expr, anyhow::Error> {
+    fn factor(&mut self) -> Result<Expr<'src>, ParseError<'src>> {
         let mut expr = self.unary()?;
         while let Some(token_type) = self.current_token() {
             match *token_type {
@@ -95,7 +746,11 @@ impl Parser {
                     self.advance();
                     let operator = self.previous();
                     let right = self.unary()?;
-                    expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
+                    let span = expr.span.to(right.span);
+                    expr = Expr::new(
+                        ExprKind::Binary(Box::new(expr), operator, Box::new(right)),
+                        span,
+                    );
                 }
                 _ => break,
             }
@@ -104,23 +759,81 @@ impl Parser {
         Ok(expr)
     }
 
-    fn unary(&mut self) -> Result<Expr, anyhow::Error> {
-        if let Some(token_type) = self.current_token() {
-            match *token_type {
-                TokenType::Bang | TokenType::Minus => {
+    fn unary(&mut self) -> Result<Expr<'src>, ParseError<'src>> {
+        if let Some(TokenType::Bang | TokenType::Minus) = self.current_token() {
+            self.advance();
+            let operator = self.previous();
+            let right = self.unary()?;
+            let span = operator.span.to(right.span);
+            return Ok(Expr::new(ExprKind::Unary(operator, Box::new(right)), span));
+        }
+
+        self.call()
+    }
+
+    fn call(&mut self) -> Result<Expr<'src>, ParseError<'src>> {
+        let mut expr = self.primary()?;
+
+        loop {
+            match self.current_token() {
+                Some(TokenType::LeftParen) => {
+                    let paren = self.advance();
+                    expr = self.finish_call(expr, &paren)?;
+                }
+                Some(TokenType::Dot) => {
                     self.advance();
-                    let operator = self.previous();
-                    let right = self.unary()?;
-                    return Ok(Expr::Unary(operator, Box::new(right)));
+                    let name = self.consume_identifier("Expect property name after '.'.")?;
+                    let span = expr.span.to(name.span);
+                    expr = Expr::new(ExprKind::Get(Box::new(expr), name), span);
                 }
-                _ => (),
+                _ => break,
+            }
+        }
+
+        Ok(expr)
+    }
+
+    fn finish_call(
+        &mut self,
+        callee: Expr<'src>,
+        opening: &Token<'src>,
+    ) -> Result<Expr<'src>, ParseError<'src>> {
+        let mut arguments = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if arguments.len() >= 255 {
+                    self.errors.push(
+                        ParseError::new(
+                            ParseErrorKind::TooManyArguments,
+                            self.peek().clone(),
+                            "Can't have more than 255 arguments.",
+                        )
+                        .into(),
+                    );
+                }
+                arguments.push(self.expression()?);
+
+                if !self.check(TokenType::Comma) {
+                    break;
+                }
+                self.advance();
             }
         }
 
-        self.primary()
+        let paren = self.consume_closing(
+            TokenType::RightParen,
+            "Expect ')' after arguments.",
+            opening,
+        )?;
+
+        let span = callee.span.to(paren.span);
+        Ok(Expr::new(
+            ExprKind::Call(Box::new(callee), paren, arguments),
+            span,
+        ))
     }
 
-    fn primary(&mut self) -> Result<Expr, anyhow::Error> {
+    fn primary(&mut self) -> Result<Expr<'src>, ParseError<'src>> {
         if let Some(token_type) = self.current_token() {
             match token_type {
                 TokenType::False
@@ -128,40 +841,174 @@ impl Parser {
                 | TokenType::Nil
                 | TokenType::Number(_)
                 | TokenType::String(_) => {
-                    self.advance();
-                    return Ok(Expr::Literal(self.previous()));
+                    let token = self.advance();
+                    let span = token.span;
+                    return Ok(Expr::new(ExprKind::Literal(token), span));
+                }
+                TokenType::Super => {
+                    let keyword = self.advance();
+                    self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
+                    let method = self.consume_identifier("Expect superclass method name.")?;
+                    let span = keyword.span.to(method.span);
+                    return Ok(Expr::new(
+                        ExprKind::Super(keyword, method, next_expr_id()),
+                        span,
+                    ));
+                }
+                TokenType::This => {
+                    let keyword = self.advance();
+                    let span = keyword.span;
+                    return Ok(Expr::new(ExprKind::This(keyword, next_expr_id()), span));
+                }
+                TokenType::Identifier => {
+                    let name = self.advance();
+                    let span = name.span;
+                    return Ok(Expr::new(ExprKind::Variable(name, next_expr_id()), span));
                 }
                 TokenType::LeftParen => {
-                    self.advance();
+                    let paren = self.advance();
                     let expr = self.expression()?;
-                    self.consume(TokenType::RightParen, "Expect ')' after expression.");
-                    return Ok(Expr::Grouping(Box::new(expr)));
+                    self.consume_closing(
+                        TokenType::RightParen,
+                        "Expect ')' after expression.",
+                        &paren,
+                    )?;
+                    return Ok(Expr::new(
+                        ExprKind::Grouping(Box::new(expr)),
+                        self.span_from(paren.span),
+                    ));
                 }
-                // Change these to errors and return a Result instead
                 _ => (),
             }
         }
-        Err(anyhow::anyhow!("Expect expression."))
+        Err(ParseError::new(
+            ParseErrorKind::ExpectedExpression,
+            self.peek().clone(),
+            "Expect expression.",
+        ))
     }
 
-    fn consume(&mut self, token_type: TokenType, message: &str) -> Token {
+    fn consume(
+        &mut self,
+        token_type: TokenType<'src>,
+        message: &str,
+    ) -> Result<Token<'src>, ParseError<'src>> {
         if self.check(token_type) {
-            return self.advance();
+            return Ok(self.advance());
+        }
+
+        Err(ParseError::new(
+            ParseErrorKind::ExpectedToken,
+            self.peek().clone(),
+            message,
+        ))
+    }
+
+    // Like `consume`, but points back at the delimiter left unclosed.
+    fn consume_closing(
+        &mut self,
+        token_type: TokenType<'src>,
+        message: &str,
+        opening: &Token<'src>,
+    ) -> Result<Token<'src>, ParseError<'src>> {
+        let closing = match token_type {
+            TokenType::RightParen => ")",
+            TokenType::RightBrace => "}",
+            _ => "",
+        };
+        self.consume(token_type, message).map_err(|error| {
+            error.with_label(
+                opening.span,
+                &format!(
+                    "expected '{}' to close '{}' opened here",
+                    closing, opening.lexeme
+                ),
+            )
+        })
+    }
+
+    fn consume_identifier(&mut self, message: &str) -> Result<Token<'src>, ParseError<'src>> {
+        if let Some(TokenType::Identifier) = self.current_token() {
+            return Ok(self.advance());
         }
 
-        panic!("{}", message);
+        Err(ParseError::new(
+            ParseErrorKind::ExpectedToken,
+            self.peek().clone(),
+            message,
+        ))
     }
 
-    fn advance(&mut self) -> Token {
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.previous().span)
+    }
+
+    fn advance(&mut self) -> Token<'src> {
         if !self.is_at_end() {
-            self.current += 1;
+            let next = self.next_token();
+            self.previous = std::mem::replace(&mut self.current, next);
         }
 
         self.previous()
     }
 
-    fn previous(&self) -> Token {
-        self.tokens[self.current - 1].clone()
+    fn shared_source(&mut self) -> Rc<Source> {
+        let source = self.source.get_or_insert_with(|| {
+            Rc::new(Source {
+                name: self.file.clone(),
+                text: self.scanner.source().to_string(),
+            })
+        });
+        Rc::clone(source)
+    }
+
+    // Once a string or comment has swallowed the rest of the input, anything
+    // said about the end of the input only repeats the scan error.
+    fn record(&mut self, error: ParseError<'src>) {
+        if self.quiet_at != Some(error.token.span.offset) {
+            self.errors.push(error.into());
+        }
+    }
+
+    // Scan errors are recorded and the offending text skipped, or replaced by
+    // a placeholder for a malformed literal, so the parser only ever sees
+    // well-formed tokens. Doc comments are set aside for whichever
+    // declaration the returned token starts.
+    fn next_token(&mut self) -> Token<'src> {
+        self.doc.clear();
+        let mut skipped = false;
+        loop {
+            match self.scanner.next() {
+                Some(Ok(Token {
+                    token_type: TokenType::DocComment(text),
+                    ..
+                })) => self.doc.push(text),
+                Some(Ok(token)) => {
+                    if skipped {
+                        self.quiet_at = Some(token.span.offset);
+                    }
+                    return token;
+                }
+                Some(Err(error)) => {
+                    self.errors.push(error.into());
+                    skipped = true;
+                }
+                None => return self.current.clone(),
+            }
+        }
+    }
+
+    fn take_doc(&mut self) -> Option<String> {
+        if self.doc.is_empty() {
+            return None;
+        }
+        let doc = self.doc.join("\n");
+        self.doc.clear();
+        Some(doc)
+    }
+
+    fn previous(&self) -> Token<'src> {
+        self.previous.clone()
     }
 
     fn check(&self, token_type: TokenType) -> bool {
@@ -172,22 +1019,21 @@ impl Parser {
         self.peek().token_type == token_type
     }
 
-    fn current_token(&self) -> Option<&TokenType> {
+    fn current_token(&self) -> Option<&TokenType<'src>> {
         if self.is_at_end() {
             return None;
         }
-        Some(&self.tokens[self.current].token_type)
+        Some(&self.current.token_type)
     }
 
     fn is_at_end(&self) -> bool {
         self.peek().token_type == TokenType::Eof
     }
 
-    fn peek(&self) -> &Token {
-        &self.tokens[self.current]
+    fn peek(&self) -> &Token<'src> {
+        &self.current
     }
-
-    fn _synchronize(&mut self) {
+    fn synchronize(&mut self) {
         self.advance();
 
         while !self.is_at_end() {
@@ -211,3 +1057,43 @@ impl Parser {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Vec<Stmt<'_>> {
+        let (statements, errors) = Parser::new(Scanner::new(source)).parse();
+        assert!(errors.is_empty(), "{:?}", errors);
+        statements
+    }
+
+    #[test]
+    fn doc_comments_attach_to_the_declaration_below() {
+        let statements = parse(
+            "/// A point.\n/// Two lines.\nclass P {\n  /// Makes one.\n  init() {}\n}\n\
+             /// Adds.\nfun add(a, b) { return a + b; }\n/// The answer.\nvar x = 42;\n",
+        );
+
+        let StmtKind::Class(_, _, methods) = &statements[0].kind else {
+            panic!("expected a class");
+        };
+        assert_eq!(statements[0].doc.as_deref(), Some("A point.\nTwo lines."));
+        assert_eq!(methods[0].doc.as_deref(), Some("Makes one."));
+
+        let StmtKind::Function(function) = &statements[1].kind else {
+            panic!("expected a function");
+        };
+        assert_eq!(function.doc.as_deref(), Some("Adds."));
+        assert_eq!(statements[1].doc, None);
+
+        assert_eq!(statements[2].doc.as_deref(), Some("The answer."));
+    }
+
+    #[test]
+    fn doc_comments_before_statements_are_dropped() {
+        let statements = parse("/// Dropped.\nprint 1;\nvar x;\n");
+        assert_eq!(statements[0].doc, None);
+        assert_eq!(statements[1].doc, None);
+    }
+}
@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::error::{ResolveError, ResolveErrorKind};
+use crate::interpreter::Interpreter;
+use crate::parser::{Expr, ExprId, ExprKind, FunctionDecl, Stmt, StmtKind};
+use crate::token::{Lexeme, Token};
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+pub struct Resolver<'a, 'src> {
+    interpreter: &'a mut Interpreter,
+    // Each scope maps a name to whether its initializer has finished resolving.
+    scopes: Vec<HashMap<Lexeme<'src>, bool>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    errors: Vec<ResolveError<'src>>,
+}
+
+impl<'a, 'src> Resolver<'a, 'src> {
+    pub fn new(interpreter: &'a mut Interpreter) -> Resolver<'a, 'src> {
+        Resolver {
+            interpreter,
+            scopes: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            errors: Vec::new(),
+        }
+    }
+
+    // Like the parser, keeps going after an error so that every problem in
+    // the program is reported at once.
+    pub fn resolve(&mut self, statements: &[Stmt<'src>]) -> Result<(), Vec<ResolveError<'src>>> {
+        self.resolve_stmts(statements);
+        self.finish()
+    }
+
+    pub(crate) fn resolve_expr(
+        &mut self,
+        expr: &Expr<'src>,
+    ) -> Result<(), Vec<ResolveError<'src>>> {
+        self.resolve_expression(expr);
+        self.finish()
+    }
+
+    fn finish(&mut self) -> Result<(), Vec<ResolveError<'src>>> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    fn resolve_stmts(&mut self, statements: &[Stmt<'src>]) {
+        for statement in statements {
+            self.resolve_stmt(statement);
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: &Stmt<'src>) {
+        match &stmt.kind {
+            StmtKind::Block(statements) => {
+                self.begin_scope();
+                self.resolve_stmts(statements);
+                self.end_scope();
+            }
+            StmtKind::Class(name, superclass, methods) => {
+                let enclosing_class = self.current_class;
+                self.current_class = ClassType::Class;
+                self.resolve_class(name, superclass.as_ref(), methods);
+                self.current_class = enclosing_class;
+            }
+            StmtKind::Expression(expr) | StmtKind::Print(expr) => self.resolve_expression(expr),
+            StmtKind::Function(declaration) => {
+                self.declare(&declaration.name);
+                self.define(&declaration.name);
+                self.resolve_function(declaration, FunctionType::Function);
+            }
+            StmtKind::If(condition, then_branch, else_branch) => {
+                self.resolve_expression(condition);
+                self.resolve_stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_stmt(else_branch);
+                }
+            }
+            StmtKind::Return(keyword, value) => {
+                if self.current_function == FunctionType::None {
+                    self.error(
+                        ResolveErrorKind::TopLevelReturn,
+                        keyword,
+                        "Can't return from top-level code.",
+                    );
+                }
+                if let Some(value) = value {
+                    if self.current_function == FunctionType::Initializer {
+                        self.error(
+                            ResolveErrorKind::ReturnFromInitializer,
+                            keyword,
+                            "Can't return a value from an initializer.",
+                        );
+                    }
+                    self.resolve_expression(value);
+                }
+            }
+            StmtKind::Var(name, initializer) => {
+                self.declare(name);
+                if let Some(initializer) = initializer {
+                    self.resolve_expression(initializer);
+                }
+                self.define(name);
+            }
+            StmtKind::While(condition, body) => {
+                self.resolve_expression(condition);
+                self.resolve_stmt(body);
+            }
+        }
+    }
+
+    fn resolve_class(
+        &mut self,
+        name: &Token<'src>,
+        superclass: Option<&Expr<'src>>,
+        methods: &[Rc<FunctionDecl>],
+    ) {
+        self.declare(name);
+        self.define(name);
+
+        if let Some(superclass) = superclass {
+            self.current_class = ClassType::Subclass;
+            self.resolve_expression(superclass);
+
+            self.begin_scope();
+            self.put("super");
+        }
+
+        self.begin_scope();
+        self.put("this");
+
+        for method in methods {
+            let function_type = if method.name.lexeme.as_str() == "init" {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.resolve_function(method, function_type);
+        }
+
+        self.end_scope();
+        if superclass.is_some() {
+            self.end_scope();
+        }
+    }
+
+    fn resolve_function(&mut self, declaration: &FunctionDecl, function_type: FunctionType) {
+        let enclosing_function = self.current_function;
+        self.current_function = function_type;
+
+        self.begin_scope();
+        for param in &declaration.params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve_stmts(&declaration.body);
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+    }
+
+    fn resolve_expression(&mut self, expr: &Expr<'src>) {
+        match &expr.kind {
+            ExprKind::Assign(name, value, id) => {
+                self.resolve_expression(value);
+                self.resolve_local(name, *id);
+            }
+            ExprKind::Binary(left, _, right) | ExprKind::Logical(left, _, right) => {
+                self.resolve_expression(left);
+                self.resolve_expression(right);
+            }
+            ExprKind::Call(callee, _, arguments) => {
+                self.resolve_expression(callee);
+                for argument in arguments {
+                    self.resolve_expression(argument);
+                }
+            }
+            ExprKind::Get(object, _) => self.resolve_expression(object),
+            ExprKind::Grouping(expr) | ExprKind::Unary(_, expr) => self.resolve_expression(expr),
+            ExprKind::Literal(_) => (),
+            ExprKind::Set(object, _, value) => {
+                self.resolve_expression(value);
+                self.resolve_expression(object);
+            }
+            ExprKind::Super(keyword, _, id) => {
+                match self.current_class {
+                    ClassType::None => self.error(
+                        ResolveErrorKind::SuperOutsideClass,
+                        keyword,
+                        "Can't use 'super' outside of a class.",
+                    ),
+                    ClassType::Class => self.error(
+                        ResolveErrorKind::SuperWithoutSuperclass,
+                        keyword,
+                        "Can't use 'super' in a class with no superclass.",
+                    ),
+                    ClassType::Subclass => (),
+                }
+                self.resolve_local(keyword, *id);
+            }
+            ExprKind::This(keyword, id) => {
+                if self.current_class == ClassType::None {
+                    self.error(
+                        ResolveErrorKind::ThisOutsideClass,
+                        keyword,
+                        "Can't use 'this' outside of a class.",
+                    );
+                }
+                self.resolve_local(keyword, *id);
+            }
+            ExprKind::Variable(name, id) => {
+                if let Some(false) = self
+                    .scopes
+                    .last()
+                    .and_then(|scope| scope.get(name.lexeme.as_str()))
+                {
+                    self.error(
+                        ResolveErrorKind::ReadInOwnInitializer,
+                        name,
+                        "Can't read local variable in its own initializer.",
+                    );
+                }
+                self.resolve_local(name, *id);
+            }
+        }
+    }
+
+    fn resolve_local(&mut self, name: &Token<'src>, id: ExprId) {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if scope.contains_key(name.lexeme.as_str()) {
+                self.interpreter.resolve(id, depth);
+                return;
+            }
+        }
+        // Not found in any local scope, so it is assumed to be global.
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token<'src>) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        if scope.contains_key(name.lexeme.as_str()) {
+            self.error(
+                ResolveErrorKind::AlreadyDeclared,
+                name,
+                "Already a variable with this name in this scope.",
+            );
+        } else {
+            scope.insert(name.lexeme.clone(), false);
+        }
+    }
+
+    fn define(&mut self, name: &Token<'src>) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.clone(), true);
+        }
+    }
+
+    fn error(&mut self, kind: ResolveErrorKind, token: &Token<'src>, message: &str) {
+        self.errors
+            .push(ResolveError::new(kind, token.clone(), message));
+    }
+
+    fn put(&mut self, name: &'static str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(Lexeme::Static(name), true);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn errors(source: &str) -> Vec<ResolveErrorKind> {
+        let (statements, errors) = Parser::new(Scanner::new(source)).parse();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let mut interpreter = Interpreter::new();
+        match Resolver::new(&mut interpreter).resolve(&statements) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.into_iter().map(|error| error.kind).collect(),
+        }
+    }
+
+    #[test]
+    fn reports_every_error() {
+        assert_eq!(
+            errors("return 1;\n{ var a = a; }"),
+            [
+                ResolveErrorKind::TopLevelReturn,
+                ResolveErrorKind::ReadInOwnInitializer
+            ]
+        );
+        assert_eq!(
+            errors("fun f(a, a) { this; }\nclass A { init() { return 1; } }"),
+            [
+                ResolveErrorKind::AlreadyDeclared,
+                ResolveErrorKind::ThisOutsideClass,
+                ResolveErrorKind::ReturnFromInitializer
+            ]
+        );
+    }
+
+    #[test]
+    fn valid_program_has_no_errors() {
+        assert_eq!(
+            errors("class A < B { init() { return; } m() { super.m(this); } }"),
+            []
+        );
+    }
+}
@@ -1,7 +1,10 @@
-use crate::token::{Token, TokenType};
+use std::borrow::Cow;
+
+use crate::error::{ScanError, ScanErrorKind};
+use crate::token::{Span, Token, TokenType};
 use phf::phf_map;
 
-const KEYWORDS: phf::Map<&'static str, TokenType> = phf_map! {
+pub const KEYWORDS: phf::Map<&'static str, TokenType<'static>> = phf_map! {
     "and" => TokenType::And,
     "class" => TokenType::Class,
     "else" => TokenType::Else,
@@ -20,197 +23,446 @@ const KEYWORDS: phf::Map<&'static str, TokenType> = phf_map! {
     "while" => TokenType::While,
 };
 
-pub struct Scanner {
-    source: String,
-    // TODO(mtoledo): Change this to use multipeek
-    tokens: Vec<Token>,
+pub struct Scanner<'src> {
+    source: &'src str,
+    // Byte offsets into `source`; always on a char boundary.
     start: usize,
     current: usize,
     line: u32,
+    // 1-based column of `current`, counted in chars.
+    column: u32,
+    start_line: u32,
+    start_column: u32,
+    // Set once the trailing `Eof` token has been handed out.
+    finished: bool,
+    // Just past the last token or error, where `Eof` goes so that errors at
+    // the end of the input point at the line they belong to.
+    end: Span,
+    // Stands in for a malformed literal right after its error, so the
+    // parser doesn't report the same mistake again as a missing expression.
+    placeholder: Option<Token<'src>>,
 }
 
-impl Scanner {
-    pub fn new(source: &str) -> Scanner {
+impl<'src> Scanner<'src> {
+    pub fn new(source: &'src str) -> Scanner<'src> {
         Scanner {
-            source: source.to_string(),
-            tokens: Vec::new(),
+            source,
             start: 0,
             current: 0,
-            line: 0,
+            line: 1,
+            column: 1,
+            start_line: 1,
+            start_column: 1,
+            finished: false,
+            end: Span::new(0, 0, 1, 1),
+            placeholder: None,
         }
     }
 
-    pub fn scan_tokens(&mut self) -> Vec<Token> {
-        while !self.is_at_end() {
-            // We are at the beginning of the next lexeme.
-            self.start = self.current;
-            self.scan_token();
-        }
-
-        self.tokens
-            .push(Token::new(TokenType::Eof, "".to_string(), self.line));
-        self.tokens.clone()
+    pub fn source(&self) -> &'src str {
+        self.source
     }
 
-    fn scan_token(&mut self) {
+    // Scans the lexeme starting at `start`, returning `None` for whitespace
+    // and comments.
+    fn scan_token(&mut self) -> Result<Option<Token<'src>>, ScanError> {
         let c = self.advance();
-        match c {
-            '(' => self.add_token(TokenType::LeftParen),
-            ')' => self.add_token(TokenType::RightParen),
-            '{' => self.add_token(TokenType::LeftBrace),
-            '}' => self.add_token(TokenType::RightBrace),
-            ',' => self.add_token(TokenType::Comma),
-            '.' => self.add_token(TokenType::Dot),
-            '-' => self.add_token(TokenType::Minus),
-            '+' => self.add_token(TokenType::Plus),
-            '*' => self.add_token(TokenType::Star),
+        let token_type = match c {
+            '(' => TokenType::LeftParen,
+            ')' => TokenType::RightParen,
+            '{' => TokenType::LeftBrace,
+            '}' => TokenType::RightBrace,
+            ',' => TokenType::Comma,
+            '.' => TokenType::Dot,
+            '-' => TokenType::Minus,
+            '+' => TokenType::Plus,
+            ';' => TokenType::Semicolon,
+            '*' => TokenType::Star,
             '!' => {
-                let token_type = if self.match_('=') {
+                if self.match_('=') {
                     TokenType::BangEqual
                 } else {
                     TokenType::Bang
-                };
-                self.add_token(token_type);
+                }
             }
             '=' => {
-                let token_type = if self.match_('=') {
+                if self.match_('=') {
                     TokenType::EqualEqual
                 } else {
                     TokenType::Equal
-                };
-                self.add_token(token_type);
+                }
             }
             '<' => {
-                let token_type = if self.match_('=') {
+                if self.match_('=') {
                     TokenType::LessEqual
                 } else {
                     TokenType::Less
-                };
-                self.add_token(token_type);
+                }
             }
             '>' => {
-                let token_type = if self.match_('=') {
+                if self.match_('=') {
                     TokenType::GreaterEqual
                 } else {
                     TokenType::Greater
-                };
-                self.add_token(token_type);
+                }
             }
             '/' => {
                 if self.match_('/') {
+                    // `///` starts a doc comment, but `////` is just a comment.
+                    let is_doc = self.peek() == '/' && self.peek_next() != '/';
                     // A comment goes until the end of the line.
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
+                    if !is_doc {
+                        return Ok(None);
+                    }
+                    let text = self.lexeme()[3..].trim_end_matches('\r');
+                    TokenType::DocComment(Cow::Borrowed(text.strip_prefix(' ').unwrap_or(text)))
+                } else if self.match_('*') {
+                    self.block_comment()?;
+                    return Ok(None);
                 } else {
-                    self.add_token(TokenType::Slash);
+                    TokenType::Slash
                 }
             }
-            ' ' | '\r' | '\t' => {}
-            '\n' => self.line += 1,
-            '"' => self.string(),
+            c if c.is_whitespace() => return Ok(None),
+            '"' => self.string()?,
+            'r' if self.raw_string_hashes().is_some() => self.raw_string()?,
             _ => {
                 if self.is_digit(c) {
-                    self.number();
-                } else if self.is_alpha(c) {
-                    self.identifier();
+                    self.number(c)?
+                } else if self.is_identifier_start(c) {
+                    self.identifier()
                 } else {
-                    self.error(self.line, "Unexpected character.");
+                    return Err(
+                        self.error(ScanErrorKind::UnexpectedCharacter, "Unexpected character.")
+                    );
                 }
             }
-        }
+        };
+        Ok(Some(self.make_token(token_type)))
     }
 
     fn is_digit(&mut self, c: char) -> bool {
-        c >= '0' && c <= '9'
+        c.is_ascii_digit()
     }
 
-    fn is_alpha(&mut self, c: char) -> bool {
-        (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z') || c == '_'
+    // Identifiers follow Unicode's XID rules, plus a leading underscore.
+    fn is_identifier_start(&mut self, c: char) -> bool {
+        c == '_' || unicode_ident::is_xid_start(c)
     }
 
-    fn number(&mut self) {
-        while self.is_digit(self.peek()) {
-            self.advance();
+    fn is_identifier_continue(&mut self, c: char) -> bool {
+        unicode_ident::is_xid_continue(c)
+    }
+
+    // Decimal literals may have a fraction and an exponent (`6.02e23`), and
+    // `0x`, `0o` and `0b` introduce hex, octal and binary integers. Any of
+    // them can use `_` between digits as a separator.
+    fn number(&mut self, first: char) -> Result<TokenType<'src>, ScanError> {
+        if first == '0' {
+            let radix = match self.peek() {
+                'x' | 'X' => Some(16),
+                'o' | 'O' => Some(8),
+                'b' | 'B' => Some(2),
+                _ => None,
+            };
+            if let Some(radix) = radix {
+                self.advance();
+                return self.radix_number(radix);
+            }
         }
 
+        self.digits();
+
         // Look for a fractional part.
         if self.peek() == '.' && self.is_digit(self.peek_next()) {
             // Consume the "."
             self.advance();
+            self.digits();
+        }
+
+        if let 'e' | 'E' = self.peek() {
+            self.advance();
+            if let '+' | '-' = self.peek() {
+                self.advance();
+            }
+            if !self.is_digit(self.peek()) {
+                return Err(self.malformed_number("Expect digits after the exponent."));
+            }
+            self.digits();
+        }
 
-            while self.is_digit(self.peek()) {
+        if self.is_identifier_start(self.peek()) {
+            while self.is_identifier_continue(self.peek()) {
                 self.advance();
             }
+            return Err(self.malformed_number("Invalid suffix on number literal."));
         }
 
-        let value = self
-            .source
-            .chars()
-            .skip(self.start)
-            .take(self.current - self.start)
-            .collect::<String>();
-        // TODO: Add error handling. Convert to result
-        self.add_token(TokenType::Number(value.parse().unwrap()))
+        let text = self.lexeme();
+        if !separators_between_digits(text, 10) {
+            return Err(self.malformed_number("Digit separators must sit between two digits."));
+        }
+        match text.replace('_', "").parse::<f64>() {
+            Ok(value) if value.is_finite() => Ok(TokenType::Number(value)),
+            Ok(_) => Err(self.malformed_number("Number literal is too large.")),
+            Err(_) => Err(self.malformed_number("Invalid number literal.")),
+        }
     }
 
-    fn identifier(&mut self) {
-        while (self.is_alpha(self.peek()) || self.is_digit(self.peek())) && !self.is_at_end() {
+    fn radix_number(&mut self, radix: u32) -> Result<TokenType<'src>, ScanError> {
+        // Take every alphanumeric so a bad digit is reported as part of the
+        // literal rather than as the start of an identifier.
+        while self.is_identifier_continue(self.peek()) {
             self.advance();
         }
-        let value = self
-            .source
+
+        let name = match radix {
+            16 => "hexadecimal",
+            8 => "octal",
+            _ => "binary",
+        };
+        let digits = &self.lexeme()[2..];
+        if digits.chars().all(|c| c == '_') {
+            return Err(self.malformed_number(&format!("Expect digits in {} literal.", name)));
+        }
+        if let Some(c) = digits.chars().find(|&c| c != '_' && !c.is_digit(radix)) {
+            return Err(
+                self.malformed_number(&format!("Invalid digit '{}' in {} literal.", c, name))
+            );
+        }
+        if !separators_between_digits(digits, radix) {
+            return Err(self.malformed_number("Digit separators must sit between two digits."));
+        }
+
+        // Numbers are f64, so reject integers that would silently round.
+        let value = digits
             .chars()
-            .skip(self.start)
-            .take(self.current - self.start)
-            .collect::<String>();
-        let token_type = KEYWORDS
-            .get(&value.as_str())
+            .filter_map(|c| c.to_digit(radix))
+            .try_fold(0u64, |value, digit| {
+                value.checked_mul(radix as u64)?.checked_add(digit as u64)
+            });
+        // The cast back saturates, so u64::MAX would otherwise round trip.
+        match value.map(|value| (value, value as f64)) {
+            Some((value, number)) if number < u64::MAX as f64 && number as u64 == value => {
+                Ok(TokenType::Number(number))
+            }
+            _ => Err(self.malformed_number(&format!(
+                "The {} literal cannot be represented exactly as a number.",
+                name
+            ))),
+        }
+    }
+
+    fn digits(&mut self) {
+        while self.is_digit(self.peek()) || self.peek() == '_' {
+            self.advance();
+        }
+    }
+
+    fn malformed_number(&self, message: &str) -> ScanError {
+        self.error(ScanErrorKind::MalformedNumber, message)
+    }
+
+    fn identifier(&mut self) -> TokenType<'src> {
+        while self.is_identifier_continue(self.peek()) {
+            self.advance();
+        }
+        KEYWORDS
+            .get(self.lexeme())
             .cloned()
-            .unwrap_or(TokenType::Identifier(value));
-        self.add_token(token_type)
+            .unwrap_or(TokenType::Identifier)
+    }
+
+    // Block comments nest, so a region containing comments can itself be
+    // commented out.
+    fn block_comment(&mut self) -> Result<(), ScanError> {
+        let mut depth = 1;
+        while depth > 0 {
+            if self.is_at_end() {
+                let span = Span::new(self.start, 2, self.start_line, self.start_column);
+                return Err(self.error_at(
+                    ScanErrorKind::UnterminatedComment,
+                    span,
+                    "Unterminated block comment.",
+                ));
+            }
+
+            match (self.peek(), self.peek_next()) {
+                ('/', '*') => {
+                    self.advance();
+                    self.advance();
+                    depth += 1;
+                }
+                ('*', '/') => {
+                    self.advance();
+                    self.advance();
+                    depth -= 1;
+                }
+                _ => {
+                    self.advance();
+                }
+            }
+        }
+        Ok(())
     }
 
-    fn string(&mut self) {
+    // Regular strings may span lines and support the escapes `\n`, `\r`,
+    // `\t`, `\\`, `\"` and `\u{...}`. The value only gets its own allocation
+    // when an escape actually has to be decoded.
+    fn string(&mut self) -> Result<TokenType<'src>, ScanError> {
+        let mut decoded: Option<String> = None;
+        let mut segment_start = self.current;
+        let mut invalid_escape = None;
+
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
+            if self.peek() != '\\' {
+                self.advance();
+                continue;
             }
-            self.advance();
+
+            let value = decoded.get_or_insert_with(String::new);
+            value.push_str(&self.source[segment_start..self.current]);
+            match self.escape() {
+                Ok(c) => value.push(c),
+                Err(error) => {
+                    invalid_escape.get_or_insert(error);
+                }
+            }
+            segment_start = self.current;
         }
 
+        if self.is_at_end() {
+            return Err(self.unterminated_string(1));
+        }
+        let tail = &self.source[segment_start..self.current];
+        // The closing ".
         self.advance();
-        let value = self
-            .source
-            .chars()
-            .skip(self.start + 1)
-            .take(self.current - self.start - 2)
-            .collect::<String>();
-        self.add_token(TokenType::String(value))
+
+        if let Some(error) = invalid_escape {
+            return Err(error);
+        }
+        let value = match decoded {
+            Some(mut value) => {
+                value.push_str(tail);
+                Cow::Owned(value)
+            }
+            None => Cow::Borrowed(tail),
+        };
+        Ok(TokenType::String(value))
     }
 
-    fn peek(&self) -> char {
+    // Decodes the escape sequence starting at the backslash under `current`.
+    fn escape(&mut self) -> Result<char, ScanError> {
+        let start = self.current;
+        let (line, column) = (self.line, self.column);
+        // The backslash.
+        self.advance();
+
+        let c = match self.advance() {
+            'n' => Some('\n'),
+            'r' => Some('\r'),
+            't' => Some('\t'),
+            '\\' => Some('\\'),
+            '"' => Some('"'),
+            'u' => return self.unicode_escape(start, line, column),
+            _ => None,
+        };
+        c.ok_or_else(|| {
+            let span = Span::new(start, self.current - start, line, column);
+            self.error_at(
+                ScanErrorKind::InvalidEscape,
+                span,
+                "Invalid escape sequence.",
+            )
+        })
+    }
+
+    // `\u{...}` takes one to six hex digits naming a Unicode scalar value.
+    fn unicode_escape(&mut self, start: usize, line: u32, column: u32) -> Result<char, ScanError> {
+        let mut message =
+            "Invalid unicode escape; expected '\\u{' followed by 1 to 6 hex digits and '}'.";
+        if self.match_('{') {
+            let digits_start = self.current;
+            while self.peek().is_ascii_hexdigit() {
+                self.advance();
+            }
+            let digits = &self.source[digits_start..self.current];
+            if (1..=6).contains(&digits.len()) && self.match_('}') {
+                let c = u32::from_str_radix(digits, 16)
+                    .ok()
+                    .and_then(char::from_u32);
+                if let Some(c) = c {
+                    return Ok(c);
+                }
+                message = "Invalid unicode escape; not a Unicode scalar value.";
+            }
+        }
+
+        let span = Span::new(start, self.current - start, line, column);
+        Err(self.error_at(ScanErrorKind::InvalidEscape, span, message))
+    }
+
+    // Raw strings are written `r"..."`, or `r#"..."#` with any number of `#`s
+    // when the text itself contains quotes. Nothing inside them is escaped.
+    fn raw_string(&mut self) -> Result<TokenType<'src>, ScanError> {
+        let hashes = self.raw_string_hashes().unwrap_or(0);
+        for _ in 0..=hashes {
+            self.advance();
+        }
+        let opening = self.current - self.start;
+
+        let terminator = format!("\"{}", "#".repeat(hashes));
+        let content_start = self.current;
+        while !self.source[self.current..].starts_with(&terminator) && !self.is_at_end() {
+            self.advance();
+        }
         if self.is_at_end() {
-            return '\0';
+            return Err(self.unterminated_string(opening));
         }
-        self.source.chars().nth(self.current).unwrap()
+
+        let value = &self.source[content_start..self.current];
+        for _ in 0..terminator.len() {
+            self.advance();
+        }
+        Ok(TokenType::String(Cow::Borrowed(value)))
+    }
+
+    // After an `r`, counts the `#`s of a raw string opener, or returns `None`
+    // when the `r` just starts an identifier.
+    fn raw_string_hashes(&self) -> Option<usize> {
+        let rest = &self.source[self.current..];
+        let hashes = rest.len() - rest.trim_start_matches('#').len();
+        rest[hashes..].starts_with('"').then_some(hashes)
+    }
+
+    // Points at the opening delimiter rather than the whole rest of the file.
+    fn unterminated_string(&self, opening: usize) -> ScanError {
+        let span = Span::new(self.start, opening, self.start_line, self.start_column);
+        self.error_at(
+            ScanErrorKind::UnterminatedString,
+            span,
+            "Unterminated string.",
+        )
+    }
+
+    fn peek(&self) -> char {
+        self.source[self.current..].chars().next().unwrap_or('\0')
     }
 
     fn peek_next(&self) -> char {
-        if self.current + 1 >= self.source.len() {
-            return '\0';
-        }
-        self.source.chars().nth(self.current + 1).unwrap()
+        let mut chars = self.source[self.current..].chars();
+        chars.next();
+        chars.next().unwrap_or('\0')
     }
 
     fn match_(&mut self, expected: char) -> bool {
-        if self.is_at_end() {
+        if self.peek() != expected || self.is_at_end() {
             return false;
         }
-        if self.source.chars().nth(self.current) != Some(expected) {
-            return false;
-        }
-        self.current += 1;
+        self.advance();
         true
     }
 
@@ -219,25 +471,252 @@ impl Scanner {
     }
 
     fn advance(&mut self) -> char {
-        self.current += 1;
-        self.source.chars().nth(self.current - 1).unwrap()
+        let Some(c) = self.source[self.current..].chars().next() else {
+            return '\0';
+        };
+        self.current += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        c
     }
 
-    fn add_token(&mut self, token_type: TokenType) {
-        let text = self
-            .source
-            .chars()
-            .skip(self.start)
-            .take(self.current - self.start)
-            .collect::<String>();
-        self.tokens.push(Token::new(token_type, text, self.line));
+    fn lexeme(&self) -> &'src str {
+        &self.source[self.start..self.current]
+    }
+
+    fn span(&self) -> Span {
+        Span::new(
+            self.start,
+            self.current - self.start,
+            self.start_line,
+            self.start_column,
+        )
+    }
+
+    fn make_token(&self, token_type: TokenType<'src>) -> Token<'src> {
+        Token::new(token_type, self.lexeme(), self.span())
+    }
+
+    fn placeholder_for(&self, kind: ScanErrorKind) -> Option<Token<'src>> {
+        let token_type = match kind {
+            ScanErrorKind::MalformedNumber => TokenType::Number(0.0),
+            ScanErrorKind::InvalidEscape => TokenType::String(Cow::Borrowed("")),
+            // Nothing is left to stand in for: the character is dropped, or
+            // the input has run out.
+            ScanErrorKind::UnexpectedCharacter
+            | ScanErrorKind::UnterminatedString
+            | ScanErrorKind::UnterminatedComment => return None,
+        };
+        Some(self.make_token(token_type))
+    }
+
+    fn error(&self, kind: ScanErrorKind, message: &str) -> ScanError {
+        self.error_at(kind, self.span(), message)
+    }
+
+    fn error_at(&self, kind: ScanErrorKind, span: Span, message: &str) -> ScanError {
+        let lexeme = &self.source[span.offset..span.end()];
+        ScanError::new(kind, lexeme, span, message)
+    }
+}
+
+impl<'src> Iterator for Scanner<'src> {
+    type Item = Result<Token<'src>, ScanError>;
+
+    // Yields tokens on demand, ending with a single `Eof` token.
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(token) = self.placeholder.take() {
+            return Some(Ok(token));
+        }
+        while !self.is_at_end() {
+            // We are at the beginning of the next lexeme.
+            self.start = self.current;
+            self.start_line = self.line;
+            self.start_column = self.column;
+            if let Some(token) = self.scan_token().transpose() {
+                self.end = Span::new(self.current, 0, self.line, self.column);
+                if let Err(error) = &token {
+                    self.placeholder = self.placeholder_for(error.kind);
+                }
+                return Some(token);
+            }
+        }
+
+        if self.finished {
+            return None;
+        }
+        self.finished = true;
+        Some(Ok(Token::new(TokenType::Eof, "", self.end)))
+    }
+}
+
+fn separators_between_digits(text: &str, radix: u32) -> bool {
+    let chars = text.chars().collect::<Vec<_>>();
+    chars.iter().enumerate().all(|(i, &c)| {
+        c != '_'
+            || (i > 0
+                && chars[i - 1].is_digit(radix)
+                && chars.get(i + 1).is_some_and(|next| next.is_digit(radix)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The first token of `source`, or the kind of the first error.
+    fn first(source: &str) -> Result<TokenType<'_>, ScanErrorKind> {
+        match Scanner::new(source).next() {
+            Some(Ok(token)) => Ok(token.token_type),
+            Some(Err(error)) => Err(error.kind),
+            None => unreachable!("the scanner always ends with Eof"),
+        }
     }
 
-    fn error(&self, line: u32, message: &str) {
-        self.report(line, "", message);
+    fn number(source: &str) -> f64 {
+        match first(source) {
+            Ok(TokenType::Number(value)) => value,
+            other => panic!("{:?} scanned as {:?}", source, other),
+        }
+    }
+
+    fn string(source: &str) -> Cow<'_, str> {
+        match first(source) {
+            Ok(TokenType::String(value)) => value,
+            other => panic!("{:?} scanned as {:?}", source, other),
+        }
+    }
+
+    #[test]
+    fn decimal_numbers() {
+        assert_eq!(number("123"), 123.0);
+        assert_eq!(number("1_000_000"), 1_000_000.0);
+        assert_eq!(number("3.25"), 3.25);
+        assert_eq!(number("1e3"), 1000.0);
+        assert_eq!(number("2.5E-1"), 0.25);
+        assert_eq!(number("1e+2"), 100.0);
+        assert_eq!(number("1_0.2_5e1_0"), 10.25e10);
+    }
+
+    #[test]
+    fn radix_numbers() {
+        assert_eq!(number("0x1F"), 31.0);
+        assert_eq!(number("0XfF"), 255.0);
+        assert_eq!(number("0o17"), 15.0);
+        assert_eq!(number("0b1010"), 10.0);
+        assert_eq!(number("0b1_0"), 2.0);
+        assert_eq!(number("0xFFFF_FFFF"), 4_294_967_295.0);
+        assert_eq!(number("0x20000000000000"), 9_007_199_254_740_992.0);
+        assert_eq!(number("0x8000_0000_0000_0000"), 9_223_372_036_854_775_808.0);
+    }
+
+    #[test]
+    fn malformed_numbers() {
+        for source in [
+            "1__0",
+            "1_",
+            "1_.5",
+            "1e",
+            "1e+",
+            "1e_1",
+            "12abc",
+            "0x",
+            "0x_",
+            "0x_1",
+            "0x1_",
+            "0b12",
+            "0o8",
+            "0xG",
+            "0b",
+            "1e400",
+            "0x20000000000001",
+            "0x1_0000_0000_0000_0000",
+            "0xFFFF_FFFF_FFFF_FFFF",
+        ] {
+            assert_eq!(
+                first(source),
+                Err(ScanErrorKind::MalformedNumber),
+                "{:?}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn malformed_literal_is_followed_by_a_placeholder() {
+        let mut scanner = Scanner::new("0b12;");
+        assert!(scanner.next().is_some_and(|token| token.is_err()));
+        let placeholder = scanner.next().and_then(Result::ok).unwrap();
+        assert_eq!(placeholder.token_type, TokenType::Number(0.0));
+        assert_eq!(placeholder.lexeme.as_str(), "0b12");
+        let next = scanner.next().and_then(Result::ok).unwrap();
+        assert_eq!(next.token_type, TokenType::Semicolon);
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(string(r#""a\nb""#), "a\nb");
+        assert_eq!(string(r#""\r\t\\\"""#), "\r\t\\\"");
+        assert_eq!(string(r#""\u{41}\u{e9}\u{1F600}""#), "A\u{e9}\u{1F600}");
+        assert_eq!(string(r#""\u{10FFFF}""#), "\u{10FFFF}");
+    }
+
+    #[test]
+    fn strings_without_escapes_borrow_the_source() {
+        assert!(matches!(string(r#""plain""#), Cow::Borrowed("plain")));
+        assert!(matches!(string(r#"r"a\n""#), Cow::Borrowed(r"a\n")));
+        assert!(matches!(
+            string(r##"r#"say "hi""#"##),
+            Cow::Borrowed(r#"say "hi""#)
+        ));
+    }
+
+    #[test]
+    fn invalid_escapes() {
+        for source in [
+            r#""\q""#,
+            r#""\u41""#,
+            r#""\u{}""#,
+            r#""\u{1234567}""#,
+            r#""\u{D800}""#,
+            r#""\u{110000}""#,
+            r#""\u{41""#,
+        ] {
+            assert_eq!(
+                first(source),
+                Err(ScanErrorKind::InvalidEscape),
+                "{:?}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn unterminated_strings() {
+        for source in [r#""abc"#, r#""abc\""#, r#"r"abc"#, r##"r#"abc""##] {
+            assert_eq!(
+                first(source),
+                Err(ScanErrorKind::UnterminatedString),
+                "{:?}",
+                source
+            );
+        }
     }
 
-    fn report(&self, line: u32, where_: &str, message: &str) {
-        println!("[line {}] Error {}: {}", line, where_, message);
+    #[test]
+    fn eof_follows_the_last_token() {
+        let eof = |source| {
+            let token = Scanner::new(source).last().and_then(Result::ok).unwrap();
+            assert_eq!(token.token_type, TokenType::Eof);
+            (token.span.offset, token.span.line, token.span.column)
+        };
+        assert_eq!(eof(""), (0, 1, 1));
+        assert_eq!(eof("print 1\n"), (7, 1, 8));
+        assert_eq!(eof("{\n  x\n  // comment\n\n"), (5, 2, 4));
+        assert_eq!(eof("\"abc\n"), (5, 2, 1));
     }
 }
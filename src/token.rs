@@ -1,30 +1,170 @@
+use std::borrow::{Borrow, Cow};
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::{Deref, Range};
+use std::rc::Rc;
 
-#[derive(Debug, Clone)]
-pub struct Token {
-    pub token_type: TokenType,
-    pub lexeme: String,
+// A region of source text. `offset` and `len` are in bytes so the span can
+// slice the source directly, while `line` and `column` are 1-based and count
+// characters for display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub offset: usize,
+    pub len: usize,
     pub line: u32,
+    pub column: u32,
 }
 
-impl Token {
-    pub fn new(token_type: TokenType, lexeme: String, line: u32) -> Token {
+impl Span {
+    pub fn new(offset: usize, len: usize, line: u32, column: u32) -> Span {
+        Span {
+            offset,
+            len,
+            line,
+            column,
+        }
+    }
+
+    pub fn end(&self) -> usize {
+        self.offset + self.len
+    }
+
+    // Covers everything from the start of `self` to the end of `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            len: other.end().saturating_sub(self.offset),
+            ..self
+        }
+    }
+}
+
+// A copy of an input that outlives the run that read it, kept for the
+// functions it declared so their errors can still be shown in context.
+#[derive(Debug)]
+pub struct Source {
+    pub name: String,
+    pub text: String,
+}
+
+// The text of a token. The scanner hands out slices of the source; anything
+// the runtime keeps, such as a function body, is switched over to ranges of
+// a shared copy so it can outlive the source.
+#[derive(Clone)]
+pub enum Lexeme<'src> {
+    Borrowed(&'src str),
+    // Byte offsets into the source. They are 32-bit to keep tokens, and so
+    // every parse result, small.
+    Shared(Rc<Source>, Range<u32>),
+    // Made up by the parser or resolver rather than read from the source.
+    Static(&'static str),
+}
+
+impl Lexeme<'_> {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Lexeme::Borrowed(text) | Lexeme::Static(text) => text,
+            Lexeme::Shared(source, range) => &source.text[range.start as usize..range.end as usize],
+        }
+    }
+
+    // The input a shared lexeme was read from.
+    pub fn source(&self) -> Option<&Source> {
+        match self {
+            Lexeme::Shared(source, _) => Some(source),
+            Lexeme::Borrowed(_) | Lexeme::Static(_) => None,
+        }
+    }
+}
+
+// Only the text; a shared lexeme would otherwise print its whole source.
+impl fmt::Debug for Lexeme<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl Deref for Lexeme<'_> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for Lexeme<'_> {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq for Lexeme<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for Lexeme<'_> {}
+
+// Must agree with `str`'s hash for the `Borrow<str>` lookups.
+impl Hash for Lexeme<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+impl fmt::Display for Lexeme<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token<'src> {
+    pub token_type: TokenType<'src>,
+    pub lexeme: Lexeme<'src>,
+    pub span: Span,
+}
+
+impl<'src> Token<'src> {
+    pub fn new(token_type: TokenType<'src>, lexeme: &'src str, span: Span) -> Token<'src> {
         Token {
             token_type,
+            lexeme: Lexeme::Borrowed(lexeme),
+            span,
+        }
+    }
+
+    // `source` must hold the text this token was scanned from.
+    pub fn into_static(self, source: &Rc<Source>) -> Token<'static> {
+        let lexeme = match self.lexeme {
+            Lexeme::Borrowed(text) => {
+                debug_assert_eq!(&source.text[self.span.offset..][..text.len()], text);
+                let start = self.span.offset as u32;
+                Lexeme::Shared(Rc::clone(source), start..start + text.len() as u32)
+            }
+            Lexeme::Shared(source, range) => Lexeme::Shared(source, range),
+            Lexeme::Static(text) => Lexeme::Static(text),
+        };
+        Token {
+            token_type: self.token_type.into_static(),
             lexeme,
-            line,
+            span: self.span,
         }
     }
+
+    pub fn line(&self) -> u32 {
+        self.span.line
+    }
 }
 
-impl fmt::Display for Token {
+impl fmt::Display for Token<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:?} {}", self.token_type, self.lexeme)
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub enum TokenType {
+pub enum TokenType<'src> {
     // Single-character tokens.
     LeftParen,
     RightParen,
@@ -49,10 +189,13 @@ pub enum TokenType {
     LessEqual,
 
     // Literals.
-    Identifier(String),
-    String(String),
+    Identifier,
+    // Borrowed from the source unless escapes had to be decoded.
+    String(Cow<'src, str>),
     Number(f64),
     Bool(bool),
+    // The text of a `///` line, minus the slashes and one leading space.
+    DocComment(Cow<'src, str>),
 
     // Keywords.
     And,
@@ -74,3 +217,51 @@ pub enum TokenType {
 
     Eof,
 }
+
+impl TokenType<'_> {
+    pub fn into_static(self) -> TokenType<'static> {
+        match self {
+            TokenType::String(text) => TokenType::String(Cow::Owned(text.into_owned())),
+            TokenType::DocComment(text) => TokenType::DocComment(Cow::Owned(text.into_owned())),
+            TokenType::LeftParen => TokenType::LeftParen,
+            TokenType::RightParen => TokenType::RightParen,
+            TokenType::LeftBrace => TokenType::LeftBrace,
+            TokenType::RightBrace => TokenType::RightBrace,
+            TokenType::Comma => TokenType::Comma,
+            TokenType::Dot => TokenType::Dot,
+            TokenType::Minus => TokenType::Minus,
+            TokenType::Plus => TokenType::Plus,
+            TokenType::Semicolon => TokenType::Semicolon,
+            TokenType::Slash => TokenType::Slash,
+            TokenType::Star => TokenType::Star,
+            TokenType::Bang => TokenType::Bang,
+            TokenType::BangEqual => TokenType::BangEqual,
+            TokenType::Equal => TokenType::Equal,
+            TokenType::EqualEqual => TokenType::EqualEqual,
+            TokenType::Greater => TokenType::Greater,
+            TokenType::GreaterEqual => TokenType::GreaterEqual,
+            TokenType::Less => TokenType::Less,
+            TokenType::LessEqual => TokenType::LessEqual,
+            TokenType::Identifier => TokenType::Identifier,
+            TokenType::Number(n) => TokenType::Number(n),
+            TokenType::Bool(b) => TokenType::Bool(b),
+            TokenType::And => TokenType::And,
+            TokenType::Class => TokenType::Class,
+            TokenType::Else => TokenType::Else,
+            TokenType::False => TokenType::False,
+            TokenType::Fun => TokenType::Fun,
+            TokenType::For => TokenType::For,
+            TokenType::If => TokenType::If,
+            TokenType::Nil => TokenType::Nil,
+            TokenType::Or => TokenType::Or,
+            TokenType::Print => TokenType::Print,
+            TokenType::Return => TokenType::Return,
+            TokenType::Super => TokenType::Super,
+            TokenType::This => TokenType::This,
+            TokenType::True => TokenType::True,
+            TokenType::Var => TokenType::Var,
+            TokenType::While => TokenType::While,
+            TokenType::Eof => TokenType::Eof,
+        }
+    }
+}